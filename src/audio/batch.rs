@@ -0,0 +1,122 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use realfft::RealFftPlanner;
+use rodio::{Decoder, Source};
+use rodio::decoder::DecoderError;
+use crate::fft::fft::{AudioView, FrequencySpectrum, SpectrumError, TryIntoFrequencySpectrum};
+
+// A whole file, decoded and split into channels
+pub struct DecodedAudio {
+    pub channels: Vec<Vec<f32>>,
+    pub sample_rate: u32,
+}
+
+impl DecodedAudio {
+    pub fn open(path: &Path) -> Result<Self, BatchError> {
+        let decoder = Decoder::new(BufReader::new(File::open(path)?))?.convert_samples::<f32>();
+        let sample_rate = decoder.sample_rate();
+        let num_channels = decoder.channels().max(1) as usize;
+
+        let mut channels = vec![Vec::new(); num_channels];
+        for (index, sample) in decoder.enumerate() {
+            channels[index % num_channels].push(sample);
+        }
+
+        Ok(Self { channels, sample_rate })
+    }
+
+    pub fn duration(&self) -> Duration {
+        let length = self.channels.first().map_or(0, Vec::len);
+        Duration::from_secs_f64(length as f64 / self.sample_rate as f64)
+    }
+
+    // Successive windows of `fft_size` samples, `hop` samples apart, each turned into a spectrum per channel
+    pub fn frames(&self, fft_size: usize, hop: usize) -> Frames<'_> {
+        Frames { audio: self, fft_size, hop: hop.max(1), position: 0, planner: RealFftPlanner::new() }
+    }
+}
+
+pub struct Frame {
+    // time at the end of the window, like the live view which always looks at the latest samples
+    pub time: Duration,
+    pub spectra: Vec<FrequencySpectrum>,
+}
+
+pub struct Frames<'a> {
+    audio: &'a DecodedAudio,
+    fft_size: usize,
+    hop: usize,
+    position: usize,
+    planner: RealFftPlanner<f32>,
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Result<Frame, BatchError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.position + self.fft_size;
+        if end > self.audio.channels.first()?.len() {
+            return None;
+        }
+
+        let spectra = self.audio.channels.iter().map(|channel| {
+            // same sample order as the introspection buffers, newest first
+            let window = channel[self.position..end].iter().rev().cloned().collect::<Box<[f32]>>();
+            AudioView::new(self.audio.sample_rate, Mutex::new(window)).try_into_spectrum(&mut self.planner)
+        }).collect::<Result<Vec<_>, _>>();
+
+        self.position += self.hop;
+        let time = Duration::from_secs_f64(end as f64 / self.audio.sample_rate as f64);
+        Some(spectra.map(|spectra| Frame { time, spectra }).map_err(BatchError::from))
+    }
+}
+
+#[derive(Debug)]
+pub enum BatchError {
+    Io(std::io::Error),
+    Decode(DecoderError),
+    Spectrum(SpectrumError),
+}
+
+impl Display for BatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => Display::fmt(err, f),
+            Self::Decode(err) => Display::fmt(err, f),
+            Self::Spectrum(err) => Display::fmt(err, f)
+        }
+    }
+}
+
+impl Error for BatchError {
+    fn cause(&self) -> Option<&dyn Error> {
+        Some(match self {
+            Self::Io(err) => err,
+            Self::Decode(err) => err,
+            Self::Spectrum(err) => err
+        })
+    }
+}
+
+impl From<std::io::Error> for BatchError {
+    fn from(value: std::io::Error) -> Self {
+        BatchError::Io(value)
+    }
+}
+
+impl From<DecoderError> for BatchError {
+    fn from(value: DecoderError) -> Self {
+        BatchError::Decode(value)
+    }
+}
+
+impl From<SpectrumError> for BatchError {
+    fn from(value: SpectrumError) -> Self {
+        BatchError::Spectrum(value)
+    }
+}
@@ -0,0 +1,199 @@
+use std::f64::consts::TAU;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use rodio::Source;
+use crate::fft::fft::Hertz;
+use crate::numtools::{from_dbfs, to_dbfs};
+
+// Test signals. Amplitudes are linear peak values, 1.0 being full scale.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Signal {
+    Sine { frequency: Hertz, amplitude: f32 },
+    MultiTone { tones: Vec<(Hertz, f32)> },
+    // sweeps start over once they reach the end
+    LinearSweep { from: Hertz, to: Hertz, duration: Duration, amplitude: f32 },
+    LogSweep { from: Hertz, to: Hertz, duration: Duration, amplitude: f32 },
+    WhiteNoise { amplitude: f32 },
+    // -3 dB per octave
+    PinkNoise { amplitude: f32 },
+    // -6 dB per octave
+    BrownNoise { amplitude: f32 },
+    // single samples at `amplitude`, `frequency` times a second
+    ImpulseTrain { frequency: Hertz, amplitude: f32 },
+}
+
+impl Signal {
+    // The signals that can be cycled through in the app
+    pub fn presets() -> Vec<Signal> {
+        let sweep = Duration::from_secs(10);
+        vec![
+            Signal::Sine { frequency: Hertz(1000.0), amplitude: from_dbfs(-6.0) },
+            Signal::MultiTone { tones: vec![(Hertz(100.0), from_dbfs(-12.0)), (Hertz(1000.0), from_dbfs(-12.0)), (Hertz(10000.0), from_dbfs(-12.0))] },
+            Signal::LinearSweep { from: Hertz(20.0), to: Hertz(20000.0), duration: sweep, amplitude: from_dbfs(-6.0) },
+            Signal::LogSweep { from: Hertz(20.0), to: Hertz(20000.0), duration: sweep, amplitude: from_dbfs(-6.0) },
+            Signal::WhiteNoise { amplitude: from_dbfs(-12.0) },
+            Signal::PinkNoise { amplitude: from_dbfs(-12.0) },
+            Signal::BrownNoise { amplitude: from_dbfs(-12.0) },
+            Signal::ImpulseTrain { frequency: Hertz(10.0), amplitude: 1.0 },
+        ]
+    }
+}
+
+impl Display for Signal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let level = |amplitude: &f32| to_dbfs(*amplitude).map_or("-inf dBFS".to_string(), |level| format!("{level:.0} dBFS"));
+        match self {
+            Self::Sine { frequency, amplitude } => write!(f, "Sine {:.0} Hz, {}", frequency.0, level(amplitude)),
+            Self::MultiTone { tones } => write!(f, "Multi-tone {}", tones.iter().map(|(frequency, _)| format!("{:.0}", frequency.0)).collect::<Vec<_>>().join("/")),
+            Self::LinearSweep { from, to, duration, amplitude } => write!(f, "Linear sweep {:.0}-{:.0} Hz in {} s, {}", from.0, to.0, duration.as_secs_f32(), level(amplitude)),
+            Self::LogSweep { from, to, duration, amplitude } => write!(f, "Log sweep {:.0}-{:.0} Hz in {} s, {}", from.0, to.0, duration.as_secs_f32(), level(amplitude)),
+            Self::WhiteNoise { amplitude } => write!(f, "White noise, {}", level(amplitude)),
+            Self::PinkNoise { amplitude } => write!(f, "Pink noise, {}", level(amplitude)),
+            Self::BrownNoise { amplitude } => write!(f, "Brown noise, {}", level(amplitude)),
+            Self::ImpulseTrain { frequency, amplitude } => write!(f, "Impulses at {} Hz, {}", frequency.0, level(amplitude)),
+        }
+    }
+}
+
+// An endless mono source playing a signal. Noise is seeded the same way every time, so that runs are repeatable.
+pub struct Generator {
+    signal: Signal,
+    sample_rate: u32,
+    position: u64,
+    phases: Vec<f64>,
+    random: u32,
+    filter: [f32; 7],
+}
+
+impl Generator {
+    pub fn new(signal: Signal, sample_rate: u32) -> Self {
+        let tones = match &signal {
+            Signal::MultiTone { tones } => tones.len(),
+            _ => 1
+        };
+        Self { signal, sample_rate, position: 0, phases: vec![0.0; tones], random: 0x2545_f491, filter: [0.0; 7] }
+    }
+}
+
+// Uniform in -1..1, from a xorshift generator
+fn white(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+impl Iterator for Generator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample_rate = self.sample_rate as f64;
+        let time = self.position as f64 / sample_rate;
+        self.position += 1;
+
+        // phases are accumulated rather than computed from the time, so that they stay precise after hours of playback
+        let mut oscillator = |index: usize, frequency: f64| {
+            let phase = self.phases[index];
+            self.phases[index] = (phase + frequency / sample_rate).fract();
+            (TAU * phase).sin() as f32
+        };
+
+        // matched in place, nothing may be allocated on the audio thread
+        let sample = match self.signal {
+            Signal::Sine { frequency, amplitude } => amplitude * oscillator(0, frequency.0 as f64),
+            Signal::MultiTone { ref tones } => tones.iter().enumerate()
+                .map(|(index, (frequency, amplitude))| amplitude * oscillator(index, frequency.0 as f64))
+                .sum(),
+            Signal::LinearSweep { from, to, duration, amplitude } => {
+                let (from, to, length) = (from.0 as f64, to.0 as f64, duration.as_secs_f64());
+                let time = time % length;
+                let phase = from * time + (to - from) * time * time / (2.0 * length);
+                amplitude * (TAU * phase).sin() as f32
+            }
+            Signal::LogSweep { from, to, duration, amplitude } => {
+                let (from, to, length) = (from.0 as f64, to.0 as f64, duration.as_secs_f64());
+                let time = time % length;
+                let rate = (to / from).ln() / length;
+                let phase = from * ((rate * time).exp() - 1.0) / rate;
+                amplitude * (TAU * phase).sin() as f32
+            }
+            Signal::WhiteNoise { amplitude } => amplitude * white(&mut self.random),
+            Signal::PinkNoise { amplitude } => {
+                // Paul Kellet's refined filter, accurate to within ±0.05 dB above 9.2 Hz at 44.1 kHz
+                let white = white(&mut self.random);
+                let b = &mut self.filter;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.153852;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                b[6] = white * 0.115926;
+                // brings the peaks back to roughly -1..1
+                amplitude * pink * 0.11
+            }
+            Signal::BrownNoise { amplitude } => {
+                // leaky integrator, so that it doesn't wander off
+                let white = white(&mut self.random);
+                self.filter[0] = (self.filter[0] + 0.02 * white) / 1.02;
+                amplitude * (self.filter[0] * 3.5).clamp(-1.0, 1.0)
+            }
+            Signal::ImpulseTrain { frequency, amplitude } => {
+                let period = (sample_rate / frequency.0 as f64).round().max(1.0) as u64;
+                if (self.position - 1).is_multiple_of(period) { amplitude } else { 0.0 }
+            }
+        };
+
+        Some(sample)
+    }
+}
+
+impl Source for Generator {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use realfft::RealFftPlanner;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::audio::introspect::introspect;
+    use crate::fft::bands::spectrum_power;
+    use crate::fft::fft::{Hertz, TryIntoFrequencySpectrum};
+    use crate::fft::peaks::find_peaks;
+    use crate::fft::scaling::Scaling;
+    use crate::numtools::{from_dbfs, to_dbfs};
+
+    #[test]
+    fn sine_at_minus_six_dbfs_reads_minus_six_dbfs() {
+        let generator = Generator::new(Signal::Sine { frequency: Hertz(1000.0), amplitude: from_dbfs(-6.0) }, 48000);
+        let (introspect, introspected) = introspect(generator, Duration::from_secs(1));
+        introspected.take(48000).for_each(drop);
+
+        let spectrum = introspect.audio_views()[0].try_into_spectrum(&mut RealFftPlanner::new()).unwrap();
+
+        let peak = find_peaks(&spectrum, -60.0)[0];
+        assert!((peak.frequency.0 - 1000.0).abs() < 0.5, "peak at {} Hz", peak.frequency.0);
+
+        let level = Scaling::AmplitudePeak.level(&spectrum, peak.bin, peak.amplitude).unwrap();
+        assert!((level + 6.0).abs() < 0.1, "peak read {level} dBFS");
+
+        let level = to_dbfs((2.0 * spectrum_power(&spectrum)).sqrt()).unwrap();
+        assert!((level + 6.0).abs() < 0.1, "spectrum read {level} dBFS");
+    }
+}
@@ -135,6 +135,15 @@ impl<T: Clone + Default> Introspectable<T> {
         Self { access, sample_rate, buffer_size }
     }
 
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Samples per channel written so far, the rest of the buffer is zero padding until it fills up
+    pub fn filled(&self) -> usize {
+        self.access.iter().map(|channel| channel.read().unwrap().len()).min().unwrap_or(0)
+    }
+
     pub fn channels(&self) -> Vec<Vec<T>> {
         self.access.iter().map(|channel| {
             let read = channel.read().unwrap();
@@ -147,6 +156,14 @@ impl Introspectable<f32> {
     pub fn audio_views(&self) -> Vec<AudioView> {
         self.channels().iter().map(|channel| AudioView::new(self.sample_rate, Mutex::new(channel.clone().into_boxed_slice()))).collect()
     }
+
+    pub fn downmix(&self) -> Vec<f32> {
+        let channels = self.channels();
+        let scale = 1.0 / channels.len().max(1) as f32;
+        (0..self.buffer_size).map(|index| {
+            channels.iter().map(|channel| channel[index]).sum::<f32>() * scale
+        }).collect()
+    }
 }
 
 
@@ -0,0 +1,147 @@
+use std::f64::consts::PI;
+
+// ITU-R BS.1770 gating block of 400 ms, overlapping by 75%
+const BLOCK_DURATION: f64 = 0.4;
+const BLOCK_STEP: f64 = 0.1;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+// Second order IIR filter in direct form I
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        let output = self.b[0] * sample + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [sample, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+// The two stage K-weighting pre-filter, a high shelf modelling the head followed by a high pass.
+// Coefficients are derived from the analog prototypes so that any sample rate matches the 48 kHz tables.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let sample_rate = sample_rate as f64;
+
+    let shelf = {
+        let (frequency, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * frequency / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::new(
+            [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]
+        )
+    };
+
+    let high_pass = {
+        let (frequency, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * frequency / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0])
+    };
+
+    [shelf, high_pass]
+}
+
+// Weight of every channel in the sum, assuming the usual L R C LFE Ls Rs order for 5.1
+fn channel_weight(channel: usize, num_channels: usize) -> f64 {
+    match (channel, num_channels) {
+        (3, 6) => 0.0,
+        (4 | 5, 6) => 1.41,
+        _ => 1.0
+    }
+}
+
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+// Gated integrated loudness in LUFS as per ITU-R BS.1770-4, or None when everything is below the absolute gate
+pub fn integrated_loudness(channels: &[Vec<f32>], sample_rate: u32) -> Option<f32> {
+    let block_size = (BLOCK_DURATION * sample_rate as f64).round() as usize;
+    let step = (BLOCK_STEP * sample_rate as f64).round() as usize;
+    let length = channels.first()?.len();
+    if block_size == 0 || length < block_size {
+        return None;
+    }
+
+    let num_blocks = (length - block_size) / step + 1;
+    let mut block_powers = vec![0.0; num_blocks];
+
+    for (index, channel) in channels.iter().enumerate() {
+        let weight = channel_weight(index, channels.len());
+        if weight == 0.0 {
+            continue;
+        }
+
+        let mut filters = k_weighting(sample_rate);
+        let squared = channel.iter().map(|sample| {
+            let filtered = filters.iter_mut().fold(*sample as f64, |sample, filter| filter.process(sample));
+            filtered * filtered
+        }).collect::<Vec<_>>();
+
+        // running sum, so every block costs two lookups
+        let mut cumulative = Vec::with_capacity(squared.len() + 1);
+        cumulative.push(0.0);
+        for value in &squared {
+            cumulative.push(cumulative.last().unwrap() + value);
+        }
+
+        for (block, power) in block_powers.iter_mut().enumerate() {
+            let start = block * step;
+            *power += weight * (cumulative[start + block_size] - cumulative[start]) / block_size as f64;
+        }
+    }
+
+    let gated_mean = |threshold: f64| {
+        let gated = block_powers.iter().filter(|power| to_lufs(**power) > threshold).collect::<Vec<_>>();
+        (!gated.is_empty()).then(|| gated.iter().copied().sum::<f64>() / gated.len() as f64)
+    };
+
+    let relative_threshold = to_lufs(gated_mean(ABSOLUTE_GATE)?) + RELATIVE_GATE;
+    gated_mean(relative_threshold.max(ABSOLUTE_GATE)).map(|power| to_lufs(power) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::generator::{Generator, Signal};
+    use crate::audio::loudness::integrated_loudness;
+    use crate::fft::fft::Hertz;
+    use crate::numtools::from_dbfs;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn sine_at_minus_twenty_dbfs_reads_minus_twenty_three_lufs() {
+        // the BS.1770 reference: a 997 Hz sine at -20 dBFS in a single channel reads -23 LUFS, on either side
+        let sine = Generator::new(Signal::Sine { frequency: Hertz(997.0), amplitude: from_dbfs(-20.0) }, SAMPLE_RATE)
+            .take(10 * SAMPLE_RATE as usize)
+            .collect::<Vec<_>>();
+        let silence = vec![0.0; sine.len()];
+
+        for channels in [vec![sine.clone(), silence.clone()], vec![silence, sine]] {
+            let loudness = integrated_loudness(&channels, SAMPLE_RATE).unwrap();
+            assert!((loudness + 23.0).abs() < 0.1, "read {loudness} LUFS");
+        }
+    }
+
+    #[test]
+    fn silence_is_gated_out() {
+        let silence = vec![vec![0.0; 10 * SAMPLE_RATE as usize]; 2];
+        assert_eq!(integrated_loudness(&silence, SAMPLE_RATE), None);
+    }
+}
@@ -1 +1,6 @@
 pub mod introspect;
+pub mod pitch;
+pub mod batch;
+pub mod generator;
+pub mod loudness;
+pub mod report;
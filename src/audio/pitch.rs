@@ -0,0 +1,106 @@
+use std::ops::RangeInclusive;
+use crate::audio::introspect::Introspectable;
+use crate::fft::fft::Hertz;
+use crate::numtools::parabolic_offset;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pitch {
+    pub frequency: Hertz,
+    // 0 means no periodicity at all, 1 means a perfectly periodic signal
+    pub confidence: f32,
+}
+
+// Monophonic pitch detection using the YIN algorithm
+// (de Cheveigné & Kawahara, 2002)
+#[derive(Clone, Debug)]
+pub struct PitchDetector {
+    pub window_size: usize,
+    pub threshold: f32,
+    pub range: RangeInclusive<f32>,
+    pub silence: f32,
+}
+
+impl Default for PitchDetector {
+    fn default() -> Self {
+        Self {
+            window_size: 2048,
+            threshold: 0.15,
+            range: 40.0..=2000.0,
+            silence: 1e-4,
+        }
+    }
+}
+
+impl PitchDetector {
+    pub fn detect(&self, samples: &[f32], sample_rate: u32) -> Option<Pitch> {
+        let min_lag = (sample_rate as f32 / self.range.end()).floor().max(2.0) as usize;
+        let max_lag = (sample_rate as f32 / self.range.start()).ceil() as usize;
+
+        let window_size = self.window_size.min(samples.len().checked_sub(max_lag + 1)?);
+        if window_size == 0 || min_lag >= max_lag {
+            return None;
+        }
+
+        let energy = samples[..window_size].iter().map(|x| x * x).sum::<f32>() / window_size as f32;
+        if energy < self.silence * self.silence {
+            return None;
+        }
+
+        // difference function, then its cumulative mean normalised form
+        let mut difference = vec![0f32; max_lag + 1];
+        for lag in 1..=max_lag {
+            difference[lag] = (0..window_size).map(|i| {
+                let delta = samples[i] - samples[i + lag];
+                delta * delta
+            }).sum();
+        }
+
+        let mut normalised = vec![1f32; max_lag + 1];
+        let mut running_sum = 0f32;
+        for lag in 1..=max_lag {
+            running_sum += difference[lag];
+            normalised[lag] = if running_sum > 0.0 { difference[lag] * lag as f32 / running_sum } else { 1.0 };
+        }
+
+        // first dip under the threshold, followed down to its local minimum
+        let mut lag = (min_lag..max_lag).find(|&lag| normalised[lag] < self.threshold)?;
+        while lag + 1 < max_lag && normalised[lag + 1] < normalised[lag] {
+            lag += 1;
+        }
+
+        let refined_lag = lag as f32 + parabolic_offset(normalised[lag - 1], normalised[lag], normalised[lag + 1]);
+        let frequency = sample_rate as f32 / refined_lag;
+
+        self.range.contains(&frequency).then_some(Pitch {
+            frequency: Hertz(frequency),
+            confidence: (1.0 - normalised[lag]).clamp(0.0, 1.0),
+        })
+    }
+
+    pub fn detect_introspectable(&self, introspect: &Introspectable<f32>) -> Option<Pitch> {
+        self.detect(&introspect.downmix(), introspect.sample_rate())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::audio::generator::{Generator, Signal};
+    use crate::audio::pitch::PitchDetector;
+    use crate::fft::fft::Hertz;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn sine_is_detected_at_its_frequency() {
+        for frequency in [82.41, 440.0, 1000.0] {
+            let samples = Generator::new(Signal::Sine { frequency: Hertz(frequency), amplitude: 0.5 }, SAMPLE_RATE).take(4096).collect::<Vec<_>>();
+            let pitch = PitchDetector::default().detect(&samples, SAMPLE_RATE).unwrap();
+            assert!((pitch.frequency.0 - frequency).abs() < 1.0, "{frequency} Hz detected at {} Hz", pitch.frequency.0);
+            assert!(pitch.confidence > 0.9, "{frequency} Hz detected with confidence {}", pitch.confidence);
+        }
+    }
+
+    #[test]
+    fn silence_has_no_pitch() {
+        assert_eq!(PitchDetector::default().detect(&[0.0; 4096], SAMPLE_RATE), None);
+    }
+}
@@ -0,0 +1,119 @@
+use std::io::Write;
+use std::time::Duration;
+use crate::audio::batch::{BatchError, DecodedAudio};
+use crate::audio::loudness::integrated_loudness;
+use crate::fft::averaging::{Averager, Averaging};
+use crate::fft::export::{json_number, write_json_spectrum};
+use crate::fft::features::{self, mix};
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+use crate::fft::scaling::Scaling;
+use crate::fft::weighting::Weighting;
+use crate::numtools::to_dbfs;
+
+// Samples at or above this magnitude count as full scale. Slightly below 1.0 so that
+// integer files, whose positive maximum is one step short of 1.0, are caught too.
+const CLIP_THRESHOLD: f32 = 0.999;
+
+// A single full scale sample is legitimate, a run of them almost certainly means the waveform was cut off
+const MIN_CLIP_LENGTH: usize = 3;
+
+// A run of consecutive full scale samples on one channel
+pub struct Clip {
+    pub channel: usize,
+    pub start: Duration,
+    pub length: usize,
+}
+
+// Summary of a whole file
+pub struct Report {
+    pub duration: Duration,
+    pub sample_rate: u32,
+    pub average: Vec<FrequencySpectrum>,
+    pub sample_peaks: Vec<f32>,
+    pub loudness: Option<f32>,
+    pub centroid: Vec<(Duration, Option<Hertz>)>,
+    pub clipping: Vec<Clip>,
+}
+
+impl Report {
+    pub fn analyse(audio: &DecodedAudio, fft_size: usize, hop: usize) -> Result<Self, BatchError> {
+        let mut averager = Averager::new(Averaging::Infinite);
+        let mut centroid = Vec::new();
+        for frame in audio.frames(fft_size, hop) {
+            let frame = frame?;
+            averager.push(&frame.spectra, 0.0);
+            centroid.push((frame.time, features::centroid(&mix(&frame.spectra))));
+        }
+
+        let sample_peaks = audio.channels.iter()
+            .map(|channel| channel.iter().fold(0f32, |peak, sample| peak.max(sample.abs())))
+            .collect();
+
+        Ok(Self {
+            duration: audio.duration(),
+            sample_rate: audio.sample_rate,
+            average: averager.spectra().to_vec(),
+            sample_peaks,
+            loudness: integrated_loudness(&audio.channels, audio.sample_rate),
+            centroid,
+            clipping: find_clipping(audio),
+        })
+    }
+
+    // The average spectrum is written in `scaling`, everything else has a fixed unit
+    pub fn write_json<W: Write>(&self, mut writer: W, scaling: Scaling) -> std::io::Result<()> {
+        let optional = |value: Option<f32>| value.map_or("null".to_string(), json_number);
+
+        write!(writer, "{{\"duration\":{},\"sample_rate\":{}", self.duration.as_secs_f64(), self.sample_rate)?;
+        write!(writer, ",\"loudness\":{}", optional(self.loudness))?;
+
+        let peaks = self.sample_peaks.iter().map(|peak| optional(to_dbfs(*peak)));
+        write!(writer, ",\"sample_peaks\":[{}]", peaks.collect::<Vec<_>>().join(","))?;
+
+        let clips = self.clipping.iter().map(|clip| {
+            format!("{{\"channel\":{},\"time\":{},\"samples\":{}}}", clip.channel, clip.start.as_secs_f64(), clip.length)
+        });
+        write!(writer, ",\"clipping\":[{}]", clips.collect::<Vec<_>>().join(","))?;
+
+        let times = self.centroid.iter().map(|(time, _)| time.as_secs_f64().to_string());
+        let frequencies = self.centroid.iter().map(|(_, centroid)| optional(centroid.map(|hertz| hertz.0)));
+        write!(writer, ",\"centroid\":{{\"time\":[{}],\"frequency\":[{}]}}",
+               times.collect::<Vec<_>>().join(","),
+               frequencies.collect::<Vec<_>>().join(","))?;
+
+        write!(writer, ",\"spectrum_unit\":\"{}\",\"average_spectrum\":[", scaling.unit(Weighting::Z, false))?;
+        for (channel, spectrum) in self.average.iter().enumerate() {
+            if channel > 0 {
+                write!(writer, ",")?;
+            }
+            write_json_spectrum(&mut writer, spectrum, scaling)?;
+        }
+        writeln!(writer, "]}}")?;
+
+        writer.flush()
+    }
+}
+
+fn find_clipping(audio: &DecodedAudio) -> Vec<Clip> {
+    let mut clips = Vec::new();
+    for (channel, samples) in audio.channels.iter().enumerate() {
+        let mut start = None;
+        // one past the end is never clipped, which closes a run that lasts until the end of the file
+        for (index, clipped) in samples.iter().map(|sample| sample.abs() >= CLIP_THRESHOLD).chain([false]).enumerate() {
+            match (start, clipped) {
+                (None, true) => start = Some(index),
+                (Some(first), false) => {
+                    if index - first >= MIN_CLIP_LENGTH {
+                        let start = Duration::from_secs_f64(first as f64 / audio.sample_rate as f64);
+                        clips.push(Clip { channel, start, length: index - first });
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    clips.sort_by_key(|clip| clip.start);
+    clips
+}
@@ -0,0 +1,218 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use itertools::process_results;
+use realfft::RealFftPlanner;
+use rodio::{Decoder, Source};
+use crate::audio::batch::DecodedAudio;
+use crate::audio::report::Report;
+use crate::fft::export::{ExportFormat, SpectrumWriter, write_psd};
+use crate::fft::features::{self, Features, mix};
+use crate::fft::fft::Hertz;
+use crate::fft::mel::{MelFilterbank, Mfcc};
+use crate::fft::scaling::Scaling;
+use crate::fft::welch::Welch;
+use crate::video::{FfmpegEncoder, render_video};
+use crate::visualiser::Visualiser;
+
+const USAGE: &str = "usage:
+    audio-whiz                                                  open the visualiser
+    audio-whiz export <input> <output.csv|output.json> [fft size] [hop] [peak|rms|psd]
+                                                                write the spectrum of every frame of a file
+    audio-whiz report <input> <output.json> [fft size] [hop] [peak|rms|psd]
+                                                                write loudness, peaks, clipping, the average spectrum and
+                                                                the spectral centroid over time of a file
+    audio-whiz features <input> <output.csv> [fft size] [hop]
+                                                                write the spectral features of every frame of a file
+    audio-whiz mel <input> <output.csv> [fft size] [hop]
+                                                                write the mel spectrogram of a file in dB, one column per band
+    audio-whiz mfcc <input> <output.csv> [fft size] [hop]
+                                                                write the mel-frequency cepstral coefficients of every frame
+    audio-whiz psd <input> <output.csv|output.json> [segment size] [overlap]
+                                                                write the power spectral density of a whole file in dB/Hz,
+                                                                averaged over overlapping segments (Welch's method)
+    audio-whiz video <input> <output.mp4|output.webm> [width] [height] [fps]
+                                                                render the visualiser to a video, needs ffmpeg";
+
+const DEFAULT_FFT_SIZE: usize = 8192;
+const DEFAULT_VIDEO_SIZE: (u32, u32) = (1920, 1080);
+const DEFAULT_FRAME_RATE: u32 = 60;
+
+// Runs a non-interactive command
+pub fn run(arguments: &[String]) -> Result<(), Box<dyn Error>> {
+    match arguments.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["export", input, output, ref rest @ ..] => {
+            let (fft_size, hop) = frame_sizes(rest)?;
+            export(Path::new(input), Path::new(output), fft_size, hop, scaling(rest.get(2))?)
+        }
+        ["report", input, output, ref rest @ ..] => {
+            let (fft_size, hop) = frame_sizes(rest)?;
+            report(Path::new(input), Path::new(output), fft_size, hop, scaling(rest.get(2))?)
+        }
+        ["features", input, output, ref rest @ ..] => {
+            let (fft_size, hop) = frame_sizes(rest)?;
+            features(Path::new(input), Path::new(output), fft_size, hop)
+        }
+        ["mel", input, output, ref rest @ ..] => {
+            let (fft_size, hop) = frame_sizes(rest)?;
+            mel(Path::new(input), Path::new(output), fft_size, hop)
+        }
+        ["mfcc", input, output, ref rest @ ..] => {
+            let (fft_size, hop) = frame_sizes(rest)?;
+            mfcc(Path::new(input), Path::new(output), fft_size, hop)
+        }
+        ["psd", input, output, ref rest @ ..] => {
+            let segment_size = rest.first().map_or(Ok(Welch::default().segment_size), |size| size.parse())?;
+            let overlap = rest.get(1).map_or(Ok(Welch::default().overlap), |overlap| overlap.parse())?;
+            psd(Path::new(input), Path::new(output), Welch { segment_size, overlap })
+        }
+        ["video", input, output, ref rest @ ..] => {
+            let width = rest.first().map_or(Ok(DEFAULT_VIDEO_SIZE.0), |width| width.parse())?;
+            let height = rest.get(1).map_or(Ok(DEFAULT_VIDEO_SIZE.1), |height| height.parse())?;
+            let frame_rate = rest.get(2).map_or(Ok(DEFAULT_FRAME_RATE), |fps| fps.parse())?;
+            video(Path::new(input), Path::new(output), (width, height), frame_rate)
+        }
+        _ => Err(USAGE.into())
+    }
+}
+
+// The optional fft size and hop that most commands start with, half overlapping frames by default
+fn frame_sizes(rest: &[&str]) -> Result<(usize, usize), Box<dyn Error>> {
+    let fft_size = rest.first().map_or(Ok(DEFAULT_FFT_SIZE), |size| size.parse())?;
+    let hop = rest.get(1).map_or(Ok(fft_size / 2), |hop| hop.parse())?;
+    if fft_size < 2 {
+        return Err("the fft size must be at least 2".into());
+    }
+    if hop == 0 {
+        return Err("the hop must be above zero".into());
+    }
+
+    Ok((fft_size, hop))
+}
+
+// Levels are read as peak amplitudes unless asked otherwise
+fn scaling(name: Option<&&str>) -> Result<Scaling, Box<dyn Error>> {
+    name.map_or(Ok(Scaling::AmplitudePeak), |name| Scaling::from_name(name).ok_or_else(|| "the scaling must be peak, rms or psd".into()))
+}
+
+fn export(input: &Path, output: &Path, fft_size: usize, hop: usize, scaling: Scaling) -> Result<(), Box<dyn Error>> {
+    let format = ExportFormat::from_path(output).ok_or("the output file must end in .csv or .json")?;
+    let audio = DecodedAudio::open(input)?;
+
+    let mut writer = SpectrumWriter::new(BufWriter::new(File::create(output)?), format, scaling)?;
+    for frame in audio.frames(fft_size, hop) {
+        let frame = frame?;
+        writer.write(frame.time, &frame.spectra)?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+fn report(input: &Path, output: &Path, fft_size: usize, hop: usize, scaling: Scaling) -> Result<(), Box<dyn Error>> {
+    let audio = DecodedAudio::open(input)?;
+    let report = Report::analyse(&audio, fft_size, hop)?;
+    report.write_json(BufWriter::new(File::create(output)?), scaling)?;
+
+    match report.loudness {
+        Some(loudness) => eprintln!("integrated loudness {loudness:.1} LUFS"),
+        None => eprintln!("integrated loudness below the gate, the file is silent")
+    }
+    if !report.clipping.is_empty() {
+        eprintln!("found {} clipped passages", report.clipping.len());
+    }
+
+    Ok(())
+}
+
+fn features(input: &Path, output: &Path, fft_size: usize, hop: usize) -> Result<(), Box<dyn Error>> {
+    let audio = DecodedAudio::open(input)?;
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    writeln!(writer, "time,{}", Features::NAMES.join(","))?;
+
+    process_results(audio.frames(fft_size, hop), |frames| {
+        for (time, features) in features::features(frames.map(|frame| (frame.time, mix(&frame.spectra)))) {
+            let values = features.values().map(|value| value.to_string());
+            writeln!(writer, "{},{}", time.as_secs_f64(), values.join(","))?;
+        }
+        writer.flush()
+    })??;
+
+    Ok(())
+}
+
+fn mel(input: &Path, output: &Path, fft_size: usize, hop: usize) -> Result<(), Box<dyn Error>> {
+    let audio = DecodedAudio::open(input)?;
+    let filterbank = MelFilterbank::default();
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    let centres = filterbank.centres(Hertz((audio.sample_rate / 2) as f32)).iter().map(|centre| centre.0.to_string()).collect::<Vec<_>>();
+    writeln!(writer, "time,{}", centres.join(","))?;
+
+    for frame in audio.frames(fft_size, hop) {
+        let frame = frame?;
+        let levels = filterbank.apply_db(&mix(&frame.spectra)).into_iter().map(|level| level.to_string()).collect::<Vec<_>>();
+        writeln!(writer, "{},{}", frame.time.as_secs_f64(), levels.join(","))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn mfcc(input: &Path, output: &Path, fft_size: usize, hop: usize) -> Result<(), Box<dyn Error>> {
+    let audio = DecodedAudio::open(input)?;
+    let mfcc = Mfcc::default();
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    writeln!(writer, "time,{}", (0..mfcc.coefficients).map(|index| format!("c{index}")).collect::<Vec<_>>().join(","))?;
+
+    for frame in audio.frames(fft_size, hop) {
+        let frame = frame?;
+        let coefficients = mfcc.compute(&mix(&frame.spectra)).into_iter().map(|coefficient| coefficient.to_string()).collect::<Vec<_>>();
+        writeln!(writer, "{},{}", frame.time.as_secs_f64(), coefficients.join(","))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn psd(input: &Path, output: &Path, welch: Welch) -> Result<(), Box<dyn Error>> {
+    let format = ExportFormat::from_path(output).ok_or("the output file must end in .csv or .json")?;
+    if welch.segment_size < 2 {
+        return Err("the segment size must be at least 2".into());
+    }
+    if !(0.0..1.0).contains(&welch.overlap) {
+        return Err("the overlap must be at least 0 and below 1".into());
+    }
+
+    let audio = DecodedAudio::open(input)?;
+    let mut planner = RealFftPlanner::new();
+    let densities = audio.channels.iter()
+        .map(|channel| welch.estimate(channel, audio.sample_rate, &mut planner))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    write_psd(BufWriter::new(File::create(output)?), &densities, format)?;
+    eprintln!("averaged {} segments per channel", densities.first().map_or(0, |psd| psd.segments));
+
+    Ok(())
+}
+
+fn video(input: &Path, output: &Path, size: (u32, u32), frame_rate: u32) -> Result<(), Box<dyn Error>> {
+    // yuv420p halves the chroma resolution, so odd sizes are rejected by the encoders
+    if !size.0.is_multiple_of(2) || !size.1.is_multiple_of(2) {
+        return Err("the video width and height must be even".into());
+    }
+    if frame_rate == 0 {
+        return Err("the frame rate must be above zero".into());
+    }
+
+    let source = Decoder::new(BufReader::new(File::open(input)?))?.convert_samples::<f32>();
+    let mut encoder = FfmpegEncoder::spawn(output, input, size, frame_rate)?;
+
+    let frames = render_video(source, &mut Visualiser::default(), &mut encoder, size, frame_rate)?;
+    eprintln!("rendered {frames} frames to {}", output.display());
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+use crate::numtools::{lerp, lerp_index_fn};
+
+// Floor for decibel-domain averaging, so that silent bins don't drag the average to -inf
+const DECIBEL_FLOOR: f32 = 1e-10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Domain {
+    Power,
+    Decibel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Averaging {
+    // time constant in seconds, independent of the frame rate
+    Exponential { time_constant: f32, domain: Domain },
+    // mean power of the last `frames` frames
+    Linear { frames: usize },
+    // mean power of every frame since the last reset
+    Infinite,
+    MaxHold,
+    MinHold,
+}
+
+impl Display for Averaging {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exponential { time_constant, domain: Domain::Power } => write!(f, "Exponential ({time_constant} s, power)"),
+            Self::Exponential { time_constant, domain: Domain::Decibel } => write!(f, "Exponential ({time_constant} s, dB)"),
+            Self::Linear { frames } => write!(f, "Linear ({frames} frames)"),
+            Self::Infinite => write!(f, "Infinite"),
+            Self::MaxHold => write!(f, "Max hold"),
+            Self::MinHold => write!(f, "Min hold"),
+        }
+    }
+}
+
+// Averages successive spectra of every channel according to an averaging mode
+pub struct Averager {
+    pub mode: Averaging,
+    state: Vec<FrequencySpectrum>,
+    history: VecDeque<Vec<FrequencySpectrum>>,
+    count: usize,
+}
+
+impl Averager {
+    pub fn new(mode: Averaging) -> Self {
+        Self { mode, state: Vec::new(), history: VecDeque::new(), count: 0 }
+    }
+
+    pub fn reset(&mut self) {
+        self.state.clear();
+        self.history.clear();
+        self.count = 0;
+    }
+
+    pub fn spectra(&self) -> &[FrequencySpectrum] {
+        &self.state
+    }
+
+    pub fn push(&mut self, spectra: &[FrequencySpectrum], delta_time: f32) {
+        // a change in channel count or buffer size starts the average over
+        let compatible = self.state.len() == spectra.len()
+            && self.state.iter().zip(spectra).all(|(old, new)| old.len() == new.len());
+        if !compatible {
+            self.reset();
+        }
+
+        self.count += 1;
+        if self.state.is_empty() {
+            self.state = spectra.to_vec();
+            if let Averaging::Linear { .. } = self.mode {
+                self.history.push_back(spectra.to_vec());
+            }
+            return;
+        }
+
+        let count = self.count as f32;
+        let combine = |old: &FrequencySpectrum, new: &FrequencySpectrum| -> FrequencySpectrum {
+            match self.mode {
+                Averaging::Exponential { time_constant, domain } => {
+                    let t = 1.0 - (-delta_time / time_constant).exp();
+                    merge_bins(new, old, |new, old| match domain {
+                        Domain::Power => {
+                            let power: f32 = lerp(old * old, new * new, t);
+                            power.sqrt()
+                        }
+                        Domain::Decibel => {
+                            let (old, new) = (old.max(DECIBEL_FLOOR).log10(), new.max(DECIBEL_FLOOR).log10());
+                            10f32.powf(lerp(old, new, t))
+                        }
+                    })
+                }
+                Averaging::Infinite => merge_bins(new, old, |new, old| {
+                    (old * old + (new * new - old * old) / count).sqrt()
+                }),
+                Averaging::MaxHold => merge_bins(new, old, f32::max),
+                Averaging::MinHold => merge_bins(new, old, f32::min),
+                Averaging::Linear { .. } => new.clone(),
+            }
+        };
+
+        self.state = self.state.iter().zip(spectra).map(|(old, new)| combine(old, new)).collect();
+
+        if let Averaging::Linear { frames } = self.mode {
+            self.history.push_back(spectra.to_vec());
+            while self.history.len() > frames.max(1) {
+                self.history.pop_front();
+            }
+
+            // recomputed from scratch every frame to avoid accumulating rounding errors
+            let frames = self.history.len() as f32;
+            self.state = (0..spectra.len()).map(|channel| {
+                spectra[channel].merge(&spectra[channel], |_, (frequency, _), _| {
+                    let power: f32 = self.history.iter().map(|frame| {
+                        let value = lerp_index_fn(|x| frame[channel].get(x), frame[channel].hertz_to_bin(frequency), 0f32);
+                        value * value
+                    }).sum();
+                    (power / frames).sqrt()
+                })
+            }).collect();
+        }
+    }
+}
+
+fn merge_bins<F>(new: &FrequencySpectrum, old: &FrequencySpectrum, combine: F) -> FrequencySpectrum
+where
+    F: Fn(f32, f32) -> f32
+{
+    new.merge(old, |_, (frequency, value), old| {
+        let approximate_bin = old.hertz_to_bin(frequency);
+        combine(value, lerp_index_fn(|x| old.get(x), approximate_bin, 0f32))
+    })
+}
+
+// Asymmetric smoothing for the live trace: bins rise with the attack time constant and fall with the
+// release one. Below the crossover, the release time grows by `bass_scaling` for every octave.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ballistics {
+    pub attack: f32,
+    pub release: f32,
+    pub bass_scaling: f32,
+    pub crossover: Hertz,
+}
+
+impl Default for Ballistics {
+    fn default() -> Self {
+        Self { attack: 0.02, release: 0.15, bass_scaling: 1.3, crossover: Hertz(250.0) }
+    }
+}
+
+impl Ballistics {
+    pub fn release_at(&self, hertz: Hertz) -> f32 {
+        let octaves = (self.crossover.0 / hertz.0.max(1.0)).log2().max(0.0);
+        self.release * self.bass_scaling.powf(octaves)
+    }
+
+    // Moves a single value at this frequency towards the new one
+    pub fn smooth(&self, old: f32, new: f32, hertz: Hertz, delta_time: f32) -> f32 {
+        let time_constant = if new > old { self.attack } else { self.release_at(hertz) };
+        let t = if time_constant > 0.0 { 1.0 - (-delta_time / time_constant).exp() } else { 1.0 };
+
+        lerp(old, new, t)
+    }
+
+    pub fn apply(&self, old: &FrequencySpectrum, new: &FrequencySpectrum, delta_time: f32) -> FrequencySpectrum {
+        new.merge(old, |_, (frequency, value), old| {
+            let approximate_bin = old.hertz_to_bin(frequency);
+            let old_value = lerp_index_fn(|x| old.get(x), approximate_bin, 0f32);
+
+            self.smooth(old_value, value, frequency, delta_time)
+        })
+    }
+}
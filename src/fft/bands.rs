@@ -0,0 +1,108 @@
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+use crate::fft::scaling::HANN_POWER_GAIN;
+
+// Octave frequency ratio for base-ten bands, as recommended by IEC 61260-1
+const OCTAVE_RATIO: f32 = 1.9952623; // 10^(3/10)
+
+const REFERENCE_FREQUENCY: f32 = 1000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OctaveFraction {
+    Whole,
+    Third,
+    Sixth,
+    Twelfth,
+}
+
+impl OctaveFraction {
+    pub fn denominator(&self) -> u32 {
+        match self {
+            Self::Whole => 1,
+            Self::Third => 3,
+            Self::Sixth => 6,
+            Self::Twelfth => 12,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Whole => Self::Third,
+            Self::Third => Self::Sixth,
+            Self::Sixth => Self::Twelfth,
+            Self::Twelfth => Self::Whole,
+        }
+    }
+}
+
+impl Display for OctaveFraction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Whole => write!(f, "1/1 octave"),
+            fraction => write!(f, "1/{} octave", fraction.denominator())
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Band {
+    pub centre: Hertz,
+    pub lower: Hertz,
+    pub upper: Hertz,
+    // mean square of the signal within the band
+    pub power: f32,
+}
+
+// The exact mid-band and edge frequencies of every band whose centre lies within the range
+pub fn bands(fraction: OctaveFraction, range: RangeInclusive<f32>) -> Vec<Band> {
+    let denominator = fraction.denominator() as f32;
+    let centre = |index: i32| {
+        let exponent = if fraction.denominator() % 2 == 1 {
+            index as f32 / denominator
+        } else {
+            (2 * index + 1) as f32 / (2.0 * denominator)
+        };
+        REFERENCE_FREQUENCY * OCTAVE_RATIO.powf(exponent)
+    };
+
+    let index_of = |hertz: f32| denominator * (hertz / REFERENCE_FREQUENCY).log(OCTAVE_RATIO);
+    let first = index_of(*range.start()).floor() as i32 - 1;
+    let last = index_of(*range.end()).ceil() as i32 + 1;
+
+    let half_band = OCTAVE_RATIO.powf(1.0 / (2.0 * denominator));
+    (first..=last).map(centre).filter(|hertz| range.contains(hertz)).map(|hertz| Band {
+        centre: Hertz(hertz),
+        lower: Hertz(hertz / half_band),
+        upper: Hertz(hertz * half_band),
+        power: 0.0,
+    }).collect()
+}
+
+// Sums the energy of the spectrum into each band. Bins straddling a band edge are split between
+// the neighbouring bands in proportion to their overlap, so that no energy is counted twice.
+pub fn band_levels(spectrum: &FrequencySpectrum, fraction: OctaveFraction, range: RangeInclusive<f32>) -> Vec<Band> {
+    let nyquist = spectrum.nyquist_frequency() as f32;
+    let range = *range.start()..=range.end().min(nyquist);
+
+    bands(fraction, range).into_iter().map(|band| {
+        let lower = spectrum.hertz_to_bin(band.lower);
+        let upper = spectrum.hertz_to_bin(band.upper);
+
+        let first = (lower - 0.5).floor().max(0.0) as usize;
+        let last = ((upper + 0.5).ceil() as usize).min(spectrum.len().saturating_sub(1));
+
+        let energy: f32 = (first..=last).filter_map(|bin| {
+            let overlap = ((bin as f32 + 0.5).min(upper) - (bin as f32 - 0.5).max(lower)).max(0.0);
+            spectrum.get(bin).map(|amplitude| overlap * amplitude * amplitude)
+        }).sum();
+
+        // the spectrum is one-sided, so each bin only carries half of its frequency's energy
+        Band { power: 2.0 * energy / HANN_POWER_GAIN, ..band }
+    }).collect()
+}
+
+// Mean square of the whole signal, as for a single band spanning every bin
+pub fn spectrum_power(spectrum: &FrequencySpectrum) -> f32 {
+    2.0 * spectrum.iter().map(|(_, amplitude)| amplitude * amplitude).sum::<f32>() / HANN_POWER_GAIN
+}
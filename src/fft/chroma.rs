@@ -0,0 +1,143 @@
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, RangeInclusive};
+use crate::fft::fft::{FrequencySpectrum, Hertz, Note};
+
+// Krumhansl-Kessler key profiles, starting from the tonic
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Chroma(pub [f32; 12]);
+
+impl Chroma {
+    // Folds the spectral energy within the given range into the 12 pitch classes, C first
+    pub fn from_spectrum(spectrum: &FrequencySpectrum, tuning: Hertz, range: RangeInclusive<f32>) -> Chroma {
+        let mut classes = [0f32; 12];
+        for (frequency, amplitude) in spectrum.iter().filter(|(frequency, _)| range.contains(&frequency.0)) {
+            classes[frequency.to_note(tuning).pitch_class()] += amplitude * amplitude;
+        }
+
+        Chroma(classes)
+    }
+
+    pub fn normalised(&self) -> Chroma {
+        let max = self.0.iter().cloned().fold(0f32, f32::max);
+        if max <= 0.0 {
+            return *self;
+        }
+
+        Chroma(self.0.map(|energy| energy / max))
+    }
+
+    pub fn sum(&self, other: &Chroma) -> Chroma {
+        let mut classes = self.0;
+        classes.iter_mut().zip(other.0).for_each(|(energy, other)| *energy += other);
+        Chroma(classes)
+    }
+}
+
+impl Index<usize> for Chroma {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key {
+    pub tonic: usize,
+    pub mode: Mode,
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", Note::new(self.tonic as i32).name(), match self.mode {
+            Mode::Major => "major",
+            Mode::Minor => "minor"
+        })
+    }
+}
+
+// Krumhansl-Schmuckler key finding over an exponentially decaying chroma average
+pub struct KeyEstimator {
+    average: Chroma,
+    // fraction of the running average retained per second
+    pub retention: f32,
+}
+
+impl Default for KeyEstimator {
+    fn default() -> Self {
+        Self { average: Chroma::default(), retention: 0.9 }
+    }
+}
+
+impl KeyEstimator {
+    pub fn update(&mut self, chroma: &Chroma, delta_time: f32) {
+        let kept = self.retention.powf(delta_time);
+        let incoming = chroma.normalised();
+        self.average = Chroma(std::array::from_fn(|index| self.average[index] * kept + incoming[index] * (1.0 - kept)));
+    }
+
+    pub fn reset(&mut self) {
+        self.average = Chroma::default();
+    }
+
+    // The best matching key, and its correlation with the averaged chroma
+    pub fn estimate(&self) -> Option<(Key, f32)> {
+        [(Mode::Major, MAJOR_PROFILE), (Mode::Minor, MINOR_PROFILE)].iter()
+            .flat_map(|(mode, profile)| (0..12).map(move |tonic| {
+                let rotated: [f32; 12] = std::array::from_fn(|index| profile[(index + 12 - tonic) % 12]);
+                (Key { tonic, mode: *mode }, correlation(&self.average.0, &rotated))
+            }))
+            .filter_map(|(key, correlation)| correlation.map(|correlation| (key, correlation)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> Option<f32> {
+    let mean_a = a.iter().sum::<f32>() / 12.0;
+    let mean_b = b.iter().sum::<f32>() / 12.0;
+
+    let (mut covariance, mut variance_a, mut variance_b) = (0f32, 0f32, 0f32);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        variance_a += (x - mean_a) * (x - mean_a);
+        variance_b += (y - mean_b) * (y - mean_b);
+    }
+
+    let denominator = (variance_a * variance_b).sqrt();
+    (denominator > 0.0).then(|| covariance / denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use realfft::RealFftPlanner;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::fft::chroma::{Chroma, Key, KeyEstimator, Mode};
+    use crate::fft::fft::{AudioView, Hertz, Note, TryIntoFrequencySpectrum};
+
+    #[test]
+    fn c_major_triad_is_in_c_major() {
+        let tuning = Hertz(440.0);
+        let tones = [60, 64, 67].map(|midi| (Note::new(midi).to_hertz(tuning), 0.3)).to_vec();
+        let samples = Generator::new(Signal::MultiTone { tones }, 48000).take(16384).collect::<Box<[f32]>>();
+        let spectrum = AudioView::new(48000, Mutex::new(samples)).try_into_spectrum(&mut RealFftPlanner::new()).unwrap();
+
+        let chroma = Chroma::from_spectrum(&spectrum, tuning, 55.0..=5000.0);
+        let mut estimator = KeyEstimator::default();
+        for _ in 0..100 {
+            estimator.update(&chroma, 0.1);
+        }
+
+        let (key, _) = estimator.estimate().unwrap();
+        assert_eq!(key, Key { tonic: 0, mode: Mode::Major }, "estimated {key}");
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use crate::fft::fft::FrequencySpectrum;
+use crate::fft::scaling::Scaling;
+use crate::fft::weighting::Weighting;
+use crate::fft::welch::PowerSpectralDensity;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+// Streams frames of per-channel spectra as CSV rows or as a JSON array of frames.
+// Every bin is written with its frequency, linear magnitude and its level in the scaling.
+pub struct SpectrumWriter<W: Write> {
+    writer: W,
+    format: ExportFormat,
+    scaling: Scaling,
+    frames: usize,
+}
+
+impl<W: Write> SpectrumWriter<W> {
+    pub fn new(mut writer: W, format: ExportFormat, scaling: Scaling) -> std::io::Result<Self> {
+        match format {
+            ExportFormat::Csv => writeln!(writer, "time,channel,bin,frequency,magnitude,level")?,
+            ExportFormat::Json => write!(writer, "[")?,
+        }
+
+        Ok(Self { writer, format, scaling, frames: 0 })
+    }
+
+    pub fn write(&mut self, time: Duration, spectra: &[FrequencySpectrum]) -> std::io::Result<()> {
+        let time = time.as_secs_f64();
+        match self.format {
+            ExportFormat::Csv => {
+                for (channel, spectrum) in spectra.iter().enumerate() {
+                    for (bin, (frequency, magnitude)) in spectrum.iter().enumerate() {
+                        let level = self.scaling.level(spectrum, bin as f32, magnitude).map_or(String::new(), |level| level.to_string());
+                        writeln!(self.writer, "{time},{channel},{bin},{},{magnitude},{level}", frequency.0)?;
+                    }
+                }
+            }
+            ExportFormat::Json => {
+                if self.frames > 0 {
+                    write!(self.writer, ",")?;
+                }
+
+                let sample_rate = spectra.first().map_or(0, |spectrum| spectrum.sample_rate());
+                let unit = self.scaling.unit(Weighting::Z, false);
+                write!(self.writer, "{{\"time\":{time},\"sample_rate\":{sample_rate},\"unit\":\"{unit}\",\"channels\":[")?;
+                for (channel, spectrum) in spectra.iter().enumerate() {
+                    if channel > 0 {
+                        write!(self.writer, ",")?;
+                    }
+                    write_json_spectrum(&mut self.writer, spectrum, self.scaling)?;
+                }
+                write!(self.writer, "]}}")?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if self.format == ExportFormat::Json {
+            writeln!(self.writer, "]")?;
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// Writes a single frame to a new file
+pub fn export_spectra(path: &Path, spectra: &[FrequencySpectrum], format: ExportFormat, scaling: Scaling) -> std::io::Result<()> {
+    let mut writer = SpectrumWriter::new(BufWriter::new(File::create(path)?), format, scaling)?;
+    writer.write(Duration::ZERO, spectra)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// A spectrum as a JSON object of parallel frequency, magnitude and level arrays
+pub fn write_json_spectrum<W: Write>(writer: &mut W, spectrum: &FrequencySpectrum, scaling: Scaling) -> std::io::Result<()> {
+    let frequencies = spectrum.iter().map(|(frequency, _)| json_number(frequency.0));
+    let magnitudes = spectrum.iter().map(|(_, magnitude)| json_number(magnitude));
+    let levels = spectrum.iter().enumerate()
+        .map(|(bin, (_, magnitude))| scaling.level(spectrum, bin as f32, magnitude).map_or("null".to_string(), json_number));
+
+    write!(writer, "{{\"frequency\":[{}],\"magnitude\":[{}],\"level\":[{}]}}",
+           frequencies.collect::<Vec<_>>().join(","),
+           magnitudes.collect::<Vec<_>>().join(","),
+           levels.collect::<Vec<_>>().join(","))
+}
+
+// Per-channel densities, every bin with its frequency, density and dB/Hz level
+pub fn write_psd<W: Write>(mut writer: W, densities: &[PowerSpectralDensity], format: ExportFormat) -> std::io::Result<W> {
+    match format {
+        ExportFormat::Csv => {
+            writeln!(writer, "channel,bin,frequency,density,db_per_hz")?;
+            for (channel, psd) in densities.iter().enumerate() {
+                for (bin, (frequency, density)) in psd.iter().enumerate() {
+                    let level = psd.level(bin).map_or(String::new(), |level| level.to_string());
+                    writeln!(writer, "{channel},{bin},{},{density},{level}", frequency.0)?;
+                }
+            }
+        }
+        ExportFormat::Json => {
+            let first = densities.first();
+            write!(writer, "{{\"sample_rate\":{},\"segments\":{},\"bandwidth\":{},\"channels\":[",
+                   first.map_or(0, PowerSpectralDensity::sample_rate),
+                   first.map_or(0, |psd| psd.segments),
+                   json_number(first.map_or(0.0, |psd| psd.bandwidth.0)))?;
+            for (channel, psd) in densities.iter().enumerate() {
+                if channel > 0 {
+                    write!(writer, ",")?;
+                }
+
+                let frequencies = psd.iter().map(|(frequency, _)| json_number(frequency.0));
+                let values = psd.iter().map(|(_, density)| json_number(density));
+                let levels = (0..psd.len()).map(|bin| psd.level(bin).map_or("null".to_string(), json_number));
+                write!(writer, "{{\"frequency\":[{}],\"density\":[{}],\"db_per_hz\":[{}]}}",
+                       frequencies.collect::<Vec<_>>().join(","),
+                       values.collect::<Vec<_>>().join(","),
+                       levels.collect::<Vec<_>>().join(","))?;
+            }
+            writeln!(writer, "]}}")?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(writer)
+}
+
+pub fn json_number(value: f32) -> String {
+    if value.is_finite() { value.to_string() } else { "null".to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use realfft::RealFftPlanner;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::fft::export::{ExportFormat, SpectrumWriter};
+    use crate::fft::fft::{AudioView, Hertz, TryIntoFrequencySpectrum};
+    use crate::fft::scaling::Scaling;
+
+    #[test]
+    fn csv_levels_follow_the_scaling() {
+        let samples = Generator::new(Signal::Sine { frequency: Hertz(1000.0), amplitude: 1.0 }, 48000).take(48000).collect::<Box<[f32]>>();
+        let spectrum = AudioView::new(48000, Mutex::new(samples)).try_into_spectrum(&mut RealFftPlanner::new()).unwrap();
+
+        for (scaling, expected) in [(Scaling::AmplitudePeak, 0.0), (Scaling::Rms, -3.01)] {
+            let mut writer = SpectrumWriter::new(Vec::new(), ExportFormat::Csv, scaling).unwrap();
+            writer.write(Duration::ZERO, std::slice::from_ref(&spectrum)).unwrap();
+            let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+            let row = csv.lines().find(|row| row.starts_with("0,0,1000,")).unwrap();
+            let level = row.rsplit(',').next().unwrap().parse::<f32>().unwrap();
+            assert!((level - expected).abs() < 0.05, "{scaling}: read {level} dB, expected {expected}");
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+use crate::fft::tempo::spectral_flux;
+
+// Share of the total energy below the rolloff frequency
+pub const ROLLOFF_FRACTION: f32 = 0.85;
+
+// Keeps the logarithm in the flatness measure finite for empty bins
+const POWER_FLOOR: f32 = 1e-20;
+
+// Averages the magnitudes of every channel into a single spectrum
+pub fn mix(spectra: &[FrequencySpectrum]) -> FrequencySpectrum {
+    let Some(first) = spectra.first() else {
+        return FrequencySpectrum::default();
+    };
+
+    let bins = (0..first.len())
+        .map(|bin| spectra.iter().filter_map(|spectrum| spectrum.get(bin)).sum::<f32>() / spectra.len() as f32)
+        .collect();
+    FrequencySpectrum::new(bins, first.sample_rate())
+}
+
+fn magnitude_sum(spectrum: &FrequencySpectrum) -> Option<f32> {
+    let sum = spectrum.iter().map(|(_, magnitude)| magnitude).sum::<f32>();
+    (sum > 0.0).then_some(sum)
+}
+
+// Magnitude weighted mean frequency, the "centre of mass" of the spectrum
+pub fn centroid(spectrum: &FrequencySpectrum) -> Option<Hertz> {
+    let total = magnitude_sum(spectrum)?;
+    Some(Hertz(spectrum.iter().map(|(frequency, magnitude)| frequency.0 * magnitude).sum::<f32>() / total))
+}
+
+// Magnitude weighted standard deviation around the centroid, also called the bandwidth
+pub fn spread(spectrum: &FrequencySpectrum) -> Option<Hertz> {
+    let total = magnitude_sum(spectrum)?;
+    let centre = centroid(spectrum)?.0;
+    let variance = spectrum.iter().map(|(frequency, magnitude)| (frequency.0 - centre).powi(2) * magnitude).sum::<f32>() / total;
+    Some(Hertz(variance.sqrt()))
+}
+
+// Lowest frequency below which `fraction` of the energy lies
+pub fn rolloff(spectrum: &FrequencySpectrum, fraction: f32) -> Option<Hertz> {
+    let total = spectrum.iter().map(|(_, magnitude)| magnitude * magnitude).sum::<f32>();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut cumulative = 0.0;
+    spectrum.iter().find(|(_, magnitude)| {
+        cumulative += magnitude * magnitude;
+        cumulative >= fraction * total
+    }).map(|(frequency, _)| frequency)
+}
+
+// Geometric over arithmetic mean of the power spectrum, 1 for white noise and close to 0 for a pure tone
+pub fn flatness(spectrum: &FrequencySpectrum) -> Option<f32> {
+    magnitude_sum(spectrum)?;
+
+    let count = spectrum.len() as f32;
+    let powers = spectrum.iter().map(|(_, magnitude)| (magnitude * magnitude).max(POWER_FLOOR));
+    let (log_sum, sum) = powers.fold((0.0, 0.0), |(log_sum, sum), power| (log_sum + power.ln(), sum + power));
+    Some((log_sum / count).exp() / (sum / count))
+}
+
+// Ratio of the largest magnitude to the mean magnitude
+pub fn crest(spectrum: &FrequencySpectrum) -> Option<f32> {
+    let total = magnitude_sum(spectrum)?;
+    let peak = spectrum.iter().map(|(_, magnitude)| magnitude).fold(0.0, f32::max);
+    Some(peak / (total / spectrum.len() as f32))
+}
+
+// Shannon entropy of the normalised power spectrum, divided by its maximum so that it lies in 0..1
+pub fn entropy(spectrum: &FrequencySpectrum) -> Option<f32> {
+    let total = spectrum.iter().map(|(_, magnitude)| magnitude * magnitude).sum::<f32>();
+    if total <= 0.0 || spectrum.len() < 2 {
+        return None;
+    }
+
+    let entropy = spectrum.iter()
+        .map(|(_, magnitude)| magnitude * magnitude / total)
+        .filter(|probability| *probability > 0.0)
+        .map(|probability| -probability * probability.log2())
+        .sum::<f32>();
+    Some(entropy / (spectrum.len() as f32).log2())
+}
+
+// Every descriptor of a single frame. Silent frames have none of them defined, and report 0 instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Features {
+    pub centroid: Hertz,
+    pub spread: Hertz,
+    pub rolloff: Hertz,
+    pub flatness: f32,
+    pub crest: f32,
+    // same onset-oriented flux as the beat tracker, 0 for the first frame
+    pub flux: f32,
+    pub entropy: f32,
+}
+
+impl Features {
+    pub const NAMES: [&'static str; 7] = ["centroid", "spread", "rolloff", "flatness", "crest", "flux", "entropy"];
+
+    pub fn compute(spectrum: &FrequencySpectrum, previous: Option<&FrequencySpectrum>) -> Self {
+        Self {
+            centroid: centroid(spectrum).unwrap_or(Hertz(0.0)),
+            spread: spread(spectrum).unwrap_or(Hertz(0.0)),
+            rolloff: rolloff(spectrum, ROLLOFF_FRACTION).unwrap_or(Hertz(0.0)),
+            flatness: flatness(spectrum).unwrap_or(0.0),
+            crest: crest(spectrum).unwrap_or(0.0),
+            flux: previous.map_or(0.0, |previous| spectral_flux(previous, spectrum)),
+            entropy: entropy(spectrum).unwrap_or(0.0),
+        }
+    }
+
+    // Values in the same order as `NAMES`
+    pub fn values(&self) -> [f32; 7] {
+        [self.centroid.0, self.spread.0, self.rolloff.0, self.flatness, self.crest, self.flux, self.entropy]
+    }
+}
+
+impl Display for Features {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Centroid: {:.0} Hz", self.centroid.0)?;
+        writeln!(f, "Spread: {:.0} Hz", self.spread.0)?;
+        writeln!(f, "Rolloff: {:.0} Hz", self.rolloff.0)?;
+        writeln!(f, "Flatness: {:.3}", self.flatness)?;
+        writeln!(f, "Crest: {:.1}", self.crest)?;
+        writeln!(f, "Flux: {:.3}", self.flux)?;
+        write!(f, "Entropy: {:.3}", self.entropy)
+    }
+}
+
+// Remembers the previous frame, which the flux is measured against
+#[derive(Default)]
+pub struct FeatureExtractor {
+    previous: Option<FrequencySpectrum>,
+}
+
+impl FeatureExtractor {
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn process(&mut self, spectrum: FrequencySpectrum) -> Features {
+        let features = Features::compute(&spectrum, self.previous.as_ref());
+        self.previous = Some(spectrum);
+        features
+    }
+}
+
+// Turns a stream of timed spectra into a stream of features, one for one and keeping the time of each
+pub struct FeatureStream<I> {
+    spectra: I,
+    extractor: FeatureExtractor,
+}
+
+impl<I: Iterator<Item=(Duration, FrequencySpectrum)>> Iterator for FeatureStream<I> {
+    type Item = (Duration, Features);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.spectra.next().map(|(time, spectrum)| (time, self.extractor.process(spectrum)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.spectra.size_hint()
+    }
+}
+
+pub fn features<I: IntoIterator<Item=(Duration, FrequencySpectrum)>>(spectra: I) -> FeatureStream<I::IntoIter> {
+    FeatureStream { spectra: spectra.into_iter(), extractor: FeatureExtractor::default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use realfft::RealFftPlanner;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::fft::features::{centroid, features, flatness, rolloff, ROLLOFF_FRACTION};
+    use crate::fft::fft::{AudioView, FrequencySpectrum, Hertz, TryIntoFrequencySpectrum};
+    use crate::fft::welch::Welch;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn samples(signal: Signal) -> Vec<f32> {
+        Generator::new(signal, SAMPLE_RATE).take(SAMPLE_RATE as usize).collect()
+    }
+
+    fn spectrum(samples: &[f32]) -> FrequencySpectrum {
+        AudioView::new(SAMPLE_RATE, Mutex::new(samples.into())).try_into_spectrum(&mut RealFftPlanner::new()).unwrap()
+    }
+
+    #[test]
+    fn sine_centroid_and_rolloff_sit_at_its_frequency() {
+        let spectrum = spectrum(&samples(Signal::Sine { frequency: Hertz(1000.0), amplitude: 0.5 }));
+
+        let centroid = centroid(&spectrum).unwrap().0;
+        assert!((centroid - 1000.0).abs() < 5.0, "centroid at {centroid} Hz");
+        let rolloff = rolloff(&spectrum, ROLLOFF_FRACTION).unwrap().0;
+        assert!((rolloff - 1000.0).abs() <= 1.0, "rolloff at {rolloff} Hz");
+    }
+
+    #[test]
+    fn white_noise_is_flat_and_a_sine_is_not() {
+        // a single periodogram scatters its bins and reads about exp(-γ) = 0.56 even for white noise, so average segments first
+        let flatness_of = |signal| {
+            let psd = Welch { segment_size: 1024, overlap: 0.5 }.estimate(&samples(signal), SAMPLE_RATE, &mut RealFftPlanner::new()).unwrap();
+            flatness(&psd.to_spectrum()).unwrap()
+        };
+
+        let noise = flatness_of(Signal::WhiteNoise { amplitude: 1.0 });
+        assert!(noise > 0.95, "white noise flatness {noise}");
+        let sine = flatness_of(Signal::Sine { frequency: Hertz(1000.0), amplitude: 0.5 });
+        assert!(sine < 0.01, "sine flatness {sine}");
+    }
+
+    #[test]
+    fn stationary_signal_has_no_flux() {
+        // frames ten periods apart hold the very same samples
+        let samples = samples(Signal::Sine { frequency: Hertz(1000.0), amplitude: 0.5 });
+        let frames = [0, 480, 960].map(|start| (Duration::from_millis((start + 4800) as u64 * 1000 / SAMPLE_RATE as u64), spectrum(&samples[start..start + 4800])));
+
+        let flux = features(frames).map(|(time, features)| (time.as_millis(), features.flux)).collect::<Vec<_>>();
+        assert_eq!(flux, [(100, 0.0), (110, 0.0), (120, 0.0)]);
+    }
+}
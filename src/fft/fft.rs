@@ -28,6 +28,15 @@ impl AudioView {
         }
     }
 
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // A copy of the samples, newest first like the introspection buffers
+    pub fn samples(&self) -> Result<Vec<f32>, SpectrumError> {
+        Ok(self.samples.lock()?.to_vec())
+    }
+
     pub fn subview(&self, range: Range<usize>) -> AudioView {
         AudioView {
             samples: Mutex::new(self.samples.lock().unwrap()[range].into()),
@@ -105,8 +114,62 @@ impl TryIntoFrequencySpectrum<f32> for AudioView {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
 pub struct Hertz(pub f32);
 
+// A4 at 440 Hz, the usual reference for note conversions
+pub const CONCERT_PITCH: Hertz = Hertz(440.0);
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+impl Hertz {
+    pub fn to_note(&self, reference: Hertz) -> Note {
+        // MIDI numbering, so A4 is 69 and middle C is 60
+        let semitones = 69.0 + 12.0 * (self.0 / reference.0).log2();
+        let nearest = semitones.round();
+        Note { midi: nearest as i32, cents: (semitones - nearest) * 100.0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub midi: i32,
+    pub cents: f32,
+}
+
+impl Note {
+    pub fn new(midi: i32) -> Self {
+        Self { midi, cents: 0.0 }
+    }
+
+    pub fn pitch_class(&self) -> usize {
+        self.midi.rem_euclid(12) as usize
+    }
+
+    pub fn name(&self) -> &'static str {
+        NOTE_NAMES[self.pitch_class()]
+    }
+
+    pub fn octave(&self) -> i32 {
+        self.midi.div_euclid(12) - 1
+    }
+
+    pub fn is_natural(&self) -> bool {
+        !self.name().ends_with('#')
+    }
+
+    pub fn to_hertz(&self, reference: Hertz) -> Hertz {
+        let semitones = (self.midi - 69) as f32 + self.cents / 100.0;
+        Hertz(reference.0 * 2f32.powf(semitones / 12.0))
+    }
+}
+
+impl Display for Note {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.name(), self.octave())
+    }
+}
+
 #[derive(Clone)]
 pub struct FrequencySpectrum {
     bins: Box<[f32]>,
@@ -123,6 +186,14 @@ impl Default for FrequencySpectrum {
 }
 
 impl FrequencySpectrum {
+    pub fn new(bins: Box<[f32]>, sample_rate: u32) -> Self {
+        Self { bins, sample_rate }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
     pub fn merge<R>(&self, other: &FrequencySpectrum, reconciler: R) -> FrequencySpectrum
     where
         R: Fn(&FrequencySpectrum, (Hertz, f32), &FrequencySpectrum) -> f32
@@ -137,20 +208,41 @@ impl FrequencySpectrum {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.bins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(Hertz, f32)> + '_ {
+        self.bins.iter().enumerate().map(|(index, value)| (self.bin_to_hertz(index), *value))
+    }
+
     pub fn get(&self, bin: usize) -> Option<f32> {
         self.bins.get(bin).map(|x| *x)
     }
 
     pub fn hertz_to_bin(&self, hertz: Hertz) -> f32 {
         let ratio = hertz.0 / self.nyquist_frequency() as f32;
-        ratio * self.bins.len() as f32
+        ratio * self.nyquist_bin()
     }
 
     pub fn bin_to_hertz(&self, bin: usize) -> Hertz {
-        let ratio = bin as f32 / (self.bins.len() as f32);
+        let ratio = bin as f32 / self.nyquist_bin();
         Hertz(ratio * self.nyquist_frequency() as f32)
     }
 
+    pub fn bin_width(&self) -> Hertz {
+        Hertz(self.nyquist_frequency() as f32 / self.nyquist_bin())
+    }
+
+    // the last bin sits exactly on the nyquist frequency, since there are fft_size / 2 + 1 of them
+    fn nyquist_bin(&self) -> f32 {
+        self.bins.len().saturating_sub(1).max(1) as f32
+    }
+
     pub fn nyquist_frequency(&self) -> u32 {
         self.sample_rate / 2
     }
@@ -171,4 +263,22 @@ where Idx: SliceIndex<[f32]> {
     fn index(&self, index: Idx) -> &Self::Output {
         &self.bins[index]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use realfft::RealFftPlanner;
+    use crate::fft::fft::{AudioView, Hertz, TryIntoFrequencySpectrum};
+
+    #[test]
+    fn last_bin_sits_on_nyquist() {
+        let view = AudioView::new(48000, Mutex::new(vec![0.0; 8192].into_boxed_slice()));
+        let spectrum = view.try_into_spectrum(&mut RealFftPlanner::new()).unwrap();
+        assert_eq!(spectrum.len(), 4097);
+
+        assert_eq!(spectrum.bin_to_hertz(spectrum.len() - 1), Hertz(24000.0));
+        assert_eq!(spectrum.hertz_to_bin(Hertz(24000.0)), 4096.0);
+        assert_eq!(spectrum.bin_width(), Hertz(48000.0 / 8192.0));
+    }
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::PI;
+use std::ops::RangeInclusive;
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+
+// Keeps the logarithm finite for empty mel bands
+const LOG_FLOOR: f32 = 1e-10;
+
+// HTK mel scale, linear below ~1 kHz and logarithmic above
+pub fn hertz_to_mel(hertz: Hertz) -> f32 {
+    2595.0 * (1.0 + hertz.0 / 700.0).log10()
+}
+
+pub fn mel_to_hertz(mel: f32) -> Hertz {
+    Hertz(700.0 * (10f32.powf(mel / 2595.0) - 1.0))
+}
+
+// Overlapping triangular filters, equally spaced on the mel scale, each peaking at 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct MelFilterbank {
+    pub bands: usize,
+    pub range: RangeInclusive<f32>,
+}
+
+impl Default for MelFilterbank {
+    fn default() -> Self {
+        Self { bands: 40, range: 20.0..=8000.0 }
+    }
+}
+
+impl MelFilterbank {
+    // Corners of the triangles, `bands + 2` of them. Band n rises from edge n, peaks at n + 1 and falls to n + 2.
+    pub fn edges(&self, nyquist: Hertz) -> Vec<Hertz> {
+        let lower = hertz_to_mel(Hertz(self.range.start().max(0.0)));
+        let upper = hertz_to_mel(Hertz(self.range.end().min(nyquist.0)));
+        (0..self.bands + 2)
+            .map(|index| mel_to_hertz(lower + (upper - lower) * index as f32 / (self.bands + 1) as f32))
+            .collect()
+    }
+
+    pub fn centres(&self, nyquist: Hertz) -> Vec<Hertz> {
+        let edges = self.edges(nyquist);
+        edges[1..edges.len() - 1].to_vec()
+    }
+
+    // Power in every band, as the triangle weighted sum of the power of the bins
+    pub fn apply(&self, spectrum: &FrequencySpectrum) -> Vec<f32> {
+        let edges = self.edges(Hertz(spectrum.nyquist_frequency() as f32));
+
+        edges.windows(3).map(|corners| {
+            let (lower, centre, upper) = (corners[0].0, corners[1].0, corners[2].0);
+            let first = spectrum.hertz_to_bin(corners[0]).ceil() as usize;
+            let last = (spectrum.hertz_to_bin(corners[2]).floor() as usize).min(spectrum.len().saturating_sub(1));
+
+            (first..=last).filter_map(|bin| {
+                let frequency = spectrum.bin_to_hertz(bin).0;
+                let weight = if frequency <= centre {
+                    (frequency - lower) / (centre - lower)
+                } else {
+                    (upper - frequency) / (upper - centre)
+                };
+                spectrum.get(bin).map(|magnitude| weight.max(0.0) * magnitude * magnitude)
+            }).sum()
+        }).collect()
+    }
+
+    // Mel band powers in dB, one column of a mel spectrogram
+    pub fn apply_db(&self, spectrum: &FrequencySpectrum) -> Vec<f32> {
+        self.apply(spectrum).into_iter().map(|power| 10.0 * power.max(LOG_FLOOR).log10()).collect()
+    }
+}
+
+// Mel-frequency cepstral coefficients: the orthonormal DCT-II of the log mel band powers
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mfcc {
+    pub filterbank: MelFilterbank,
+    pub coefficients: usize,
+}
+
+impl Default for Mfcc {
+    fn default() -> Self {
+        Self { filterbank: MelFilterbank::default(), coefficients: 13 }
+    }
+}
+
+impl Mfcc {
+    pub fn compute(&self, spectrum: &FrequencySpectrum) -> Vec<f32> {
+        let log_powers = self.filterbank.apply(spectrum).into_iter()
+            .map(|power| power.max(LOG_FLOOR).ln())
+            .collect::<Vec<_>>();
+        self.cepstrum(&log_powers)
+    }
+
+    fn cepstrum(&self, log_powers: &[f32]) -> Vec<f32> {
+        let bands = log_powers.len() as f32;
+        (0..self.coefficients.min(log_powers.len())).map(|k| {
+            let scale = if k == 0 { (1.0 / bands).sqrt() } else { (2.0 / bands).sqrt() };
+            scale * log_powers.iter().enumerate()
+                .map(|(m, value)| value * (PI * k as f32 * (m as f32 + 0.5) / bands).cos())
+                .sum::<f32>()
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fft::fft::{FrequencySpectrum, Hertz};
+    use crate::fft::mel::{MelFilterbank, Mfcc};
+
+    #[test]
+    fn triangles_sum_to_one_across_the_passband() {
+        let filterbank = MelFilterbank::default();
+        let centres = filterbank.centres(Hertz(24000.0));
+        let (first, last) = (centres[0], centres[centres.len() - 1]);
+
+        // a single bin of unit power reads as the sum of the weights of every band at that bin
+        let mut bins = vec![0.0; 4097];
+        for bin in 0..bins.len() {
+            bins.fill(0.0);
+            bins[bin] = 1.0;
+            let spectrum = FrequencySpectrum::new(bins.clone().into(), 48000);
+            let frequency = spectrum.bin_to_hertz(bin);
+            if frequency.0 < first.0 || frequency.0 > last.0 {
+                continue;
+            }
+
+            let sum = filterbank.apply(&spectrum).iter().sum::<f32>();
+            assert!((sum - 1.0).abs() < 1e-3, "weights sum to {sum} at {} Hz", frequency.0);
+        }
+    }
+
+    #[test]
+    fn constant_log_powers_only_have_a_c0_coefficient() {
+        let mfcc = Mfcc::default();
+        let coefficients = mfcc.cepstrum(&[-3.0; 40]);
+
+        assert!((coefficients[0] + 3.0 * 40f32.sqrt()).abs() < 1e-3, "c0 is {}", coefficients[0]);
+        for (index, coefficient) in coefficients.iter().enumerate().skip(1) {
+            assert!(coefficient.abs() < 1e-4, "c{index} is {coefficient}");
+        }
+    }
+}
@@ -1 +1,14 @@
 pub mod fft;
+pub mod chroma;
+pub mod tempo;
+pub mod peaks;
+pub mod bands;
+pub mod weighting;
+pub mod averaging;
+pub mod reference;
+pub mod export;
+pub mod features;
+pub mod mel;
+pub mod scale;
+pub mod scaling;
+pub mod welch;
@@ -0,0 +1,89 @@
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+use crate::numtools::{parabolic_offset, to_dbfs};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    pub frequency: Hertz,
+    // linear amplitude at the interpolated vertex
+    pub amplitude: f32,
+    // fractional bin index of the vertex
+    pub bin: f32,
+}
+
+// Local maxima above the threshold (in dBFS), loudest first.
+// Each peak is refined to sub-bin accuracy by fitting a parabola through the log-magnitudes
+// of the maximum and its neighbours, which is close to exact for a Hann-windowed sinusoid.
+pub fn find_peaks(spectrum: &FrequencySpectrum, threshold: f32) -> Vec<Peak> {
+    let mut peaks: Vec<Peak> = (1..spectrum.len().saturating_sub(1)).filter_map(|bin| {
+        let (left, centre, right) = (spectrum[bin - 1], spectrum[bin], spectrum[bin + 1]);
+        if centre <= left || centre < right {
+            return None;
+        }
+
+        let (left, centre, right) = (to_dbfs(left)?, to_dbfs(centre)?, to_dbfs(right)?);
+        if centre < threshold {
+            return None;
+        }
+
+        let offset = parabolic_offset(left, centre, right);
+        let level = centre - 0.25 * (left - right) * offset;
+
+        Some(Peak {
+            frequency: Hertz(spectrum.bin_to_hertz(bin).0 + offset * spectrum.bin_width().0),
+            amplitude: 10f32.powf(level / 20.0),
+            bin: bin as f32 + offset,
+        })
+    }).collect();
+
+    peaks.sort_by(|a, b| b.amplitude.total_cmp(&a.amplitude));
+    peaks
+}
+
+// The loudest `count` peaks across several spectra, ignoring peaks within `separation` of a louder one.
+// The first two bins are skipped as well as anything below `min_frequency`: the window leaks DC and rumble into them,
+// which would otherwise be interpolated into peaks at a fraction of a hertz.
+pub fn strongest_peaks<'a, I>(spectra: I, threshold: f32, count: usize, separation: Hertz, min_frequency: Hertz) -> Vec<Peak>
+where
+    I: IntoIterator<Item=&'a FrequencySpectrum>
+{
+    let mut candidates: Vec<Peak> = spectra.into_iter()
+        .flat_map(|spectrum| find_peaks(spectrum, threshold))
+        .filter(|peak| peak.bin.round() >= 2.0 && peak.frequency.0 >= min_frequency.0)
+        .collect();
+    candidates.sort_by(|a, b| b.amplitude.total_cmp(&a.amplitude));
+
+    let mut peaks: Vec<Peak> = Vec::with_capacity(count);
+    for candidate in candidates {
+        if peaks.len() >= count {
+            break;
+        }
+
+        if peaks.iter().all(|peak| (peak.frequency.0 - candidate.frequency.0).abs() > separation.0) {
+            peaks.push(candidate);
+        }
+    }
+
+    peaks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use realfft::RealFftPlanner;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::fft::fft::{AudioView, Hertz, TryIntoFrequencySpectrum};
+    use crate::fft::peaks::strongest_peaks;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn tones_next_to_dc_are_not_peaks() {
+        let tones = vec![(Hertz(1.5), 0.5), (Hertz(25.0), 0.25), (Hertz(1000.0), 0.1)];
+        let samples = Generator::new(Signal::MultiTone { tones }, SAMPLE_RATE).take(SAMPLE_RATE as usize).collect::<Vec<_>>();
+        let spectrum = AudioView::new(SAMPLE_RATE, Mutex::new(samples.into())).try_into_spectrum(&mut RealFftPlanner::new()).unwrap();
+
+        let peaks = strongest_peaks([&spectrum], -90.0, 5, Hertz(20.0), Hertz(20.0));
+        let frequencies = peaks.iter().map(|peak| peak.frequency.0.round()).collect::<Vec<_>>();
+        assert_eq!(frequencies, [25.0, 1000.0]);
+    }
+}
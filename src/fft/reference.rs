@@ -0,0 +1,119 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::num::{ParseFloatError, ParseIntError};
+use std::path::Path;
+use crate::fft::fft::FrequencySpectrum;
+use crate::numtools::lerp_index_fn;
+
+const HEADER: &str = "audio-whiz reference";
+
+// A frozen set of per-channel spectra to compare against
+#[derive(Clone)]
+pub struct Reference {
+    pub spectra: Vec<FrequencySpectrum>,
+}
+
+impl Reference {
+    pub fn capture(spectra: &[FrequencySpectrum]) -> Self {
+        Self { spectra: spectra.to_vec() }
+    }
+
+    // Amplitude ratio of each channel to the matching reference channel, for plotting in dB.
+    // Extra channels on either side are compared against the last channel of the other.
+    pub fn difference(&self, spectra: &[FrequencySpectrum]) -> Vec<FrequencySpectrum> {
+        spectra.iter().enumerate().filter_map(|(channel, spectrum)| {
+            let reference = self.spectra.get(channel).or(self.spectra.last())?;
+            Some(spectrum.merge(reference, |_, (frequency, value), reference| {
+                let reference_value = lerp_index_fn(|x| reference.get(x), reference.hertz_to_bin(frequency), 0f32);
+                value / reference_value
+            }))
+        }).collect()
+    }
+
+    // Plain text: a header line, the sample rate, then one line of space-separated bins per channel
+    pub fn save(&self, path: &Path) -> Result<(), ReferenceError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{HEADER}")?;
+        writeln!(writer, "{}", self.spectra.first().map_or(0, |spectrum| spectrum.sample_rate()))?;
+
+        for spectrum in &self.spectra {
+            let line = spectrum.iter().map(|(_, value)| value.to_string()).collect::<Vec<_>>().join(" ");
+            writeln!(writer, "{line}")?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReferenceError> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(ReferenceError::NotAReference);
+        }
+
+        let sample_rate: u32 = lines.next().transpose()?.ok_or(ReferenceError::NotAReference)?.trim().parse()?;
+
+        let mut spectra = Vec::new();
+        for line in lines {
+            let bins = line?.split_whitespace().map(str::parse).collect::<Result<Box<[f32]>, _>>()?;
+            spectra.push(FrequencySpectrum::new(bins, sample_rate));
+        }
+
+        if spectra.is_empty() {
+            return Err(ReferenceError::NotAReference);
+        }
+
+        Ok(Self { spectra })
+    }
+}
+
+#[derive(Debug)]
+pub enum ReferenceError {
+    Io(std::io::Error),
+    InvalidSampleRate(ParseIntError),
+    InvalidBin(ParseFloatError),
+    NotAReference,
+}
+
+impl Display for ReferenceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => Display::fmt(err, f),
+            Self::InvalidSampleRate(err) => write!(f, "invalid sample rate: {err}"),
+            Self::InvalidBin(err) => write!(f, "invalid bin value: {err}"),
+            Self::NotAReference => write!(f, "the file is not a reference trace")
+        }
+    }
+}
+
+impl Error for ReferenceError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::InvalidSampleRate(err) => Some(err),
+            Self::InvalidBin(err) => Some(err),
+            Self::NotAReference => None
+        }
+    }
+}
+
+impl From<std::io::Error> for ReferenceError {
+    fn from(value: std::io::Error) -> Self {
+        ReferenceError::Io(value)
+    }
+}
+
+impl From<ParseIntError> for ReferenceError {
+    fn from(value: ParseIntError) -> Self {
+        ReferenceError::InvalidSampleRate(value)
+    }
+}
+
+impl From<ParseFloatError> for ReferenceError {
+    fn from(value: ParseFloatError) -> Self {
+        ReferenceError::InvalidBin(value)
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use crate::fft::fft::Hertz;
+use crate::fft::mel::{hertz_to_mel, mel_to_hertz};
+
+// Lowest frequency shown on a logarithmic scale, which can't reach 0 Hz
+const LOG_MINIMUM: f32 = 10.0;
+
+// How frequencies are spread along an axis. Every scale maps Hertz onto its own unit and back,
+// and positions on screen are linear in that unit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrequencyScale {
+    Linear,
+    Logarithmic,
+    Mel,
+    // critical band rate, after Traunmüller (1990)
+    Bark,
+    // equivalent rectangular bandwidth rate, after Glasberg & Moore (1990)
+    Erb,
+}
+
+impl FrequencyScale {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Linear => Self::Logarithmic,
+            Self::Logarithmic => Self::Mel,
+            Self::Mel => Self::Bark,
+            Self::Bark => Self::Erb,
+            Self::Erb => Self::Linear,
+        }
+    }
+
+    pub fn forward(&self, hertz: Hertz) -> f32 {
+        match self {
+            Self::Linear => hertz.0,
+            Self::Logarithmic => hertz.0.log2(),
+            Self::Mel => hertz_to_mel(hertz),
+            Self::Bark => 26.81 * hertz.0 / (1960.0 + hertz.0) - 0.53,
+            Self::Erb => 21.4 * (1.0 + 0.00437 * hertz.0).log10(),
+        }
+    }
+
+    pub fn inverse(&self, value: f32) -> Hertz {
+        match self {
+            Self::Linear => Hertz(value),
+            Self::Logarithmic => Hertz(2f32.powf(value)),
+            Self::Mel => mel_to_hertz(value),
+            Self::Bark => Hertz(1960.0 * (value + 0.53) / (26.28 - value)),
+            Self::Erb => Hertz((10f32.powf(value / 21.4) - 1.0) / 0.00437),
+        }
+    }
+
+    // Frequencies from here up to the nyquist frequency make up the full range of the axis
+    pub fn minimum(&self) -> Hertz {
+        match self {
+            Self::Logarithmic => Hertz(LOG_MINIMUM),
+            _ => Hertz(0.0)
+        }
+    }
+
+    // Position of a frequency within the range, 0 at the start and 1 at the end
+    pub fn normalise(&self, hertz: Hertz, range: &RangeInclusive<f32>) -> f32 {
+        let (start, end) = (self.forward(Hertz(*range.start())), self.forward(Hertz(*range.end())));
+        (self.forward(hertz) - start) / (end - start)
+    }
+
+    pub fn denormalise(&self, position: f32, range: &RangeInclusive<f32>) -> Hertz {
+        let (start, end) = (self.forward(Hertz(*range.start())), self.forward(Hertz(*range.end())));
+        self.inverse(start + position * (end - start))
+    }
+}
+
+impl Display for FrequencyScale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "Linear"),
+            Self::Logarithmic => write!(f, "Log"),
+            Self::Mel => write!(f, "Mel"),
+            Self::Bark => write!(f, "Bark"),
+            Self::Erb => write!(f, "ERB"),
+        }
+    }
+}
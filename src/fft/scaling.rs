@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::f32::consts::SQRT_2;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::num::ParseFloatError;
+use std::path::Path;
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+use crate::fft::weighting::Weighting;
+use crate::numtools::to_dbfs;
+
+// Mean of the Hann window, the share of a sinusoid's amplitude left in its bin
+pub const HANN_COHERENT_GAIN: f32 = 0.5;
+
+// Mean of the squared Hann window, needed to turn summed bin powers back into signal power
+pub const HANN_POWER_GAIN: f32 = 0.375;
+
+// Level of the usual acoustic calibrator, 1 Pa
+pub const CALIBRATOR_LEVEL: f32 = 94.0;
+
+// How levels are read off the spectra, which are stored as |X| / N of a Hann windowed frame.
+// Every mode is one-sided, folding the discarded negative frequencies back into each bin, except at DC and nyquist where there are none.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scaling {
+    // peak amplitude of a sinusoid in the bin, a full-scale sine reads 0 dB
+    AmplitudePeak,
+    // its RMS value instead, so a full-scale sine reads -3 dB
+    Rms,
+    // power spectral density in dB/Hz, for noise, which doesn't depend on the fft size
+    PowerSpectralDensity,
+}
+
+impl Scaling {
+    // As given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "peak" => Some(Self::AmplitudePeak),
+            "rms" => Some(Self::Rms),
+            "psd" => Some(Self::PowerSpectralDensity),
+            _ => None
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::AmplitudePeak => Self::Rms,
+            Self::Rms => Self::PowerSpectralDensity,
+            Self::PowerSpectralDensity => Self::AmplitudePeak,
+        }
+    }
+
+    // Multiplier taking a bin's stored magnitude to this scaling, the square root of the density for PSD
+    pub fn gain(&self, spectrum: &FrequencySpectrum, bin: usize) -> f32 {
+        let one_sided = if bin == 0 || bin + 1 >= spectrum.len() { 1.0 } else { 2.0 };
+        match self {
+            Self::AmplitudePeak => one_sided / HANN_COHERENT_GAIN,
+            // only the folded bins hold sinusoids, DC is its own RMS value
+            Self::Rms => one_sided / HANN_COHERENT_GAIN / if one_sided > 1.0 { SQRT_2 } else { 1.0 },
+            Self::PowerSpectralDensity => {
+                let fft_size = 2 * spectrum.len().saturating_sub(1);
+                (one_sided * fft_size as f32 / (HANN_POWER_GAIN * spectrum.sample_rate() as f32)).sqrt()
+            }
+        }
+    }
+
+    // Level in dB of a (possibly interpolated) magnitude at a fractional bin
+    pub fn level(&self, spectrum: &FrequencySpectrum, bin: f32, magnitude: f32) -> Option<f32> {
+        to_dbfs(magnitude * self.gain(spectrum, bin.round().max(0.0) as usize))
+    }
+
+    // Level in dB of a mean square spread over a bandwidth, as for a band or the whole spectrum
+    pub fn power_level(&self, power: f32, bandwidth: Hertz) -> Option<f32> {
+        to_dbfs(match self {
+            Self::AmplitudePeak => (2.0 * power).sqrt(),
+            Self::Rms => power.sqrt(),
+            Self::PowerSpectralDensity => (power / bandwidth.0).sqrt(),
+        })
+    }
+
+    // e.g. dBFS(A) RMS, or dB(A) SPL once calibrated
+    pub fn unit(&self, weighting: Weighting, calibrated: bool) -> String {
+        let weighting = match weighting {
+            Weighting::Z => String::new(),
+            weighting => format!("({weighting})")
+        };
+
+        match (self, calibrated) {
+            (Self::AmplitudePeak, false) => format!("dBFS{weighting}"),
+            (Self::AmplitudePeak, true) => format!("dB{weighting} SPL peak"),
+            (Self::Rms, false) => format!("dBFS{weighting} RMS"),
+            (Self::Rms, true) => format!("dB{weighting} SPL"),
+            (Self::PowerSpectralDensity, false) => format!("dBFS{weighting}/Hz"),
+            (Self::PowerSpectralDensity, true) => format!("dB{weighting} SPL/Hz"),
+        }
+    }
+}
+
+impl Display for Scaling {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AmplitudePeak => write!(f, "Amplitude (peak)"),
+            Self::Rms => write!(f, "Amplitude (RMS)"),
+            Self::PowerSpectralDensity => write!(f, "Power spectral density"),
+        }
+    }
+}
+
+// Maps levels to dB SPL for a measurement microphone: the offset is the SPL of a signal with an RMS of 1.0, and is added to every level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub offset: f32,
+}
+
+impl Calibration {
+    // From the mean square read while the microphone sits in a calibrator producing `level` dB SPL
+    pub fn from_power(power: f32, level: f32) -> Option<Self> {
+        Some(Self { offset: level - Scaling::Rms.power_level(power, Hertz(1.0))? })
+    }
+
+    // Plain text holding just the offset in dB, so it can also be written by hand from a microphone's sensitivity
+    pub fn save(&self, path: &Path) -> Result<(), CalibrationError> {
+        fs::write(path, format!("{}\n", self.offset))?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CalibrationError> {
+        Ok(Self { offset: fs::read_to_string(path)?.trim().parse()? })
+    }
+}
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    Io(std::io::Error),
+    InvalidOffset(ParseFloatError),
+}
+
+impl Display for CalibrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => Display::fmt(err, f),
+            Self::InvalidOffset(err) => write!(f, "invalid calibration offset: {err}")
+        }
+    }
+}
+
+impl Error for CalibrationError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::InvalidOffset(err) => Some(err)
+        }
+    }
+}
+
+impl From<std::io::Error> for CalibrationError {
+    fn from(value: std::io::Error) -> Self {
+        CalibrationError::Io(value)
+    }
+}
+
+impl From<ParseFloatError> for CalibrationError {
+    fn from(value: ParseFloatError) -> Self {
+        CalibrationError::InvalidOffset(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use realfft::RealFftPlanner;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::fft::fft::{AudioView, FrequencySpectrum, Hertz, TryIntoFrequencySpectrum};
+    use crate::fft::scaling::Scaling;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn spectrum(signal: Signal, fft_size: usize) -> FrequencySpectrum {
+        let samples = Generator::new(signal, SAMPLE_RATE).take(fft_size).collect::<Box<[f32]>>();
+        AudioView::new(SAMPLE_RATE, Mutex::new(samples)).try_into_spectrum(&mut RealFftPlanner::new()).unwrap()
+    }
+
+    #[test]
+    fn sine_reads_its_rms_level() {
+        let spectrum = spectrum(Signal::Sine { frequency: Hertz(1000.0), amplitude: 1.0 }, 48000);
+        let level = Scaling::Rms.level(&spectrum, 1000.0, spectrum[1000]).unwrap();
+        assert!((level + 3.01).abs() < 0.05, "read {level} dB RMS");
+    }
+
+    #[test]
+    fn white_noise_density_is_independent_of_fft_size() {
+        // uniform in -1..1 has a mean square of 1/3, spread over the one-sided bandwidth
+        let expected = 10.0 * (2.0 / 3.0 / SAMPLE_RATE as f32).log10();
+
+        for fft_size in [4096, 32768] {
+            let spectrum = spectrum(Signal::WhiteNoise { amplitude: 1.0 }, fft_size);
+            let power = (1..spectrum.len() - 1)
+                .map(|bin| (spectrum[bin] * Scaling::PowerSpectralDensity.gain(&spectrum, bin)).powi(2))
+                .sum::<f32>() / (spectrum.len() - 2) as f32;
+            let density = 10.0 * power.log10();
+            assert!((density - expected).abs() < 0.5, "{fft_size}: read {density} dB/Hz, expected {expected}");
+        }
+    }
+}
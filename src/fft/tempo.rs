@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use crate::fft::fft::FrequencySpectrum;
+use crate::numtools::parabolic_offset;
+
+// Rate at which the onset envelope is sampled, in Hz
+const ENVELOPE_RATE: f32 = 100.0;
+
+// Seconds of onset envelope considered for tempo estimation
+const ENVELOPE_DURATION: f32 = 6.0;
+
+// Seconds of onset envelope used for the adaptive onset threshold
+const THRESHOLD_DURATION: f32 = 0.5;
+
+// Half-wave rectified difference of log-compressed magnitudes between two frames
+pub fn spectral_flux(previous: &FrequencySpectrum, current: &FrequencySpectrum) -> f32 {
+    if previous.len() != current.len() || current.is_empty() {
+        return 0.0;
+    }
+
+    let compress = |value: f32| (1.0 + 1000.0 * value).ln();
+    let flux: f32 = previous.iter().zip(current.iter())
+        .map(|((_, old), (_, new))| (compress(new) - compress(old)).max(0.0))
+        .sum();
+
+    flux / current.len() as f32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tempo {
+    pub bpm: f32,
+    // fraction of the current beat that has elapsed, 0 being on the beat
+    pub phase: f32,
+    pub confidence: f32,
+}
+
+pub struct BeatTracker {
+    previous: Option<FrequencySpectrum>,
+    envelope: VecDeque<f32>,
+    pending: f32,
+    pending_time: f32,
+    tempo: Option<Tempo>,
+    pub bpm_range: (f32, f32),
+}
+
+impl Default for BeatTracker {
+    fn default() -> Self {
+        Self {
+            previous: None,
+            envelope: VecDeque::with_capacity((ENVELOPE_RATE * ENVELOPE_DURATION) as usize),
+            pending: 0.0,
+            pending_time: 0.0,
+            tempo: None,
+            bpm_range: (60.0, 200.0),
+        }
+    }
+}
+
+impl BeatTracker {
+    pub fn reset(&mut self) {
+        *self = Self { bpm_range: self.bpm_range, ..Self::default() };
+    }
+
+    pub fn tempo(&self) -> Option<Tempo> {
+        self.tempo
+    }
+
+    // Feeds the next analysis frame, returning whether it contained an onset
+    pub fn process(&mut self, spectrum: FrequencySpectrum, delta_time: f32) -> bool {
+        let flux = self.previous.as_ref().map_or(0.0, |previous| spectral_flux(previous, &spectrum));
+        self.previous = Some(spectrum);
+
+        // resample the frame-rate flux onto the fixed-rate envelope, keeping the peak of each hop
+        self.pending = self.pending.max(flux);
+        self.pending_time += delta_time;
+
+        let mut onset = false;
+        let hop = 1.0 / ENVELOPE_RATE;
+        while self.pending_time >= hop {
+            self.pending_time -= hop;
+            onset |= self.push_envelope(self.pending);
+        }
+        self.pending = if self.pending_time > 0.0 { flux } else { 0.0 };
+
+        self.tempo = self.estimate_tempo();
+        onset
+    }
+
+    fn push_envelope(&mut self, value: f32) -> bool {
+        let window = (THRESHOLD_DURATION * ENVELOPE_RATE) as usize;
+        let recent = self.envelope.iter().rev().take(window);
+        let count = recent.len().max(1) as f32;
+        let mean = recent.clone().sum::<f32>() / count;
+        let deviation = (recent.map(|x| (x - mean) * (x - mean)).sum::<f32>() / count).sqrt();
+
+        let previous = self.envelope.back().copied().unwrap_or(0.0);
+        let onset = value > mean + 1.5 * deviation && value > previous && value > 0.0;
+
+        self.envelope.push_back(value);
+        while self.envelope.len() > (ENVELOPE_DURATION * ENVELOPE_RATE) as usize {
+            self.envelope.pop_front();
+        }
+
+        onset
+    }
+
+    fn estimate_tempo(&self) -> Option<Tempo> {
+        let (min_bpm, max_bpm) = (self.bpm_range.0.min(self.bpm_range.1), self.bpm_range.0.max(self.bpm_range.1));
+        if max_bpm <= 0.0 {
+            return None;
+        }
+
+        // the search looks one lag either side for the peak interpolation, so the shortest lag has to stay above 1
+        let min_lag = ((60.0 * ENVELOPE_RATE / max_bpm).floor() as usize).max(2);
+        let max_lag = ((60.0 * ENVELOPE_RATE / min_bpm).ceil() as usize).max(min_lag);
+
+        // need a few beats worth of envelope before guessing
+        if self.envelope.len() < max_lag.saturating_mul(4) {
+            return None;
+        }
+
+        let mean = self.envelope.iter().sum::<f32>() / self.envelope.len() as f32;
+        let envelope: Vec<f32> = self.envelope.iter().map(|x| x - mean).collect();
+
+        let energy: f32 = envelope.iter().map(|x| x * x).sum();
+        if energy <= f32::EPSILON {
+            return None;
+        }
+
+        let autocorrelation = |lag: usize| -> f32 {
+            envelope.iter().zip(&envelope[lag..]).map(|(a, b)| a * b).sum::<f32>() / energy
+        };
+
+        // log-gaussian prior around 120 BPM to settle octave ambiguity
+        let prior = |lag: f32| {
+            let octaves = (60.0 * ENVELOPE_RATE / lag / 120.0).log2();
+            (-0.5 * octaves * octaves / (0.8 * 0.8)).exp()
+        };
+
+        let scores: Vec<f32> = (min_lag - 1..=max_lag + 1).map(|lag| autocorrelation(lag) * prior(lag as f32)).collect();
+        let (best, score) = scores.iter().enumerate().skip(1).take(scores.len() - 2)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, score)| (index, *score))?;
+
+        if score <= 0.0 {
+            return None;
+        }
+
+        let lag = (min_lag - 1 + best) as f32 + parabolic_offset(scores[best - 1], scores[best], scores[best + 1]);
+        let period = lag.round() as usize;
+
+        // beat phase: the offset into the past at which a comb of beat-spaced taps collects the most onset energy
+        let latest = envelope.len() - 1;
+        let offset = (0..period).max_by(|&a, &b| {
+            let comb = |offset: usize| (offset..=latest).step_by(period).map(|ago| envelope[latest - ago]).sum::<f32>();
+            comb(a).total_cmp(&comb(b))
+        })?;
+
+        Some(Tempo {
+            // the interpolated lag can land half a lag outside of the range searched
+            bpm: (60.0 * ENVELOPE_RATE / lag).clamp(min_bpm, max_bpm),
+            phase: (offset as f32 + self.pending_time * ENVELOPE_RATE) / lag,
+            confidence: (score / prior(lag)).clamp(0.0, 1.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fft::fft::FrequencySpectrum;
+    use crate::fft::tempo::BeatTracker;
+
+    // A click every half second, 120 BPM
+    fn track(bpm_range: (f32, f32)) -> BeatTracker {
+        let silence = FrequencySpectrum::new(vec![0.0; 1025].into(), 48000);
+        let click = FrequencySpectrum::new(vec![1.0; 1025].into(), 48000);
+
+        let mut tracker = BeatTracker { bpm_range, ..BeatTracker::default() };
+        for frame in 0..600 {
+            let spectrum = if frame % 50 == 0 { click.clone() } else { silence.clone() };
+            tracker.process(spectrum, 0.01);
+        }
+        tracker
+    }
+
+    #[test]
+    fn clicks_every_half_second_are_120_bpm() {
+        // an inverted range is searched the right way round
+        for bpm_range in [(60.0, 200.0), (200.0, 60.0)] {
+            let bpm = track(bpm_range).tempo().unwrap().bpm;
+            assert!((bpm - 120.0).abs() <= 2.0, "{bpm_range:?}: {bpm} BPM");
+        }
+    }
+
+    #[test]
+    fn tempo_stays_inside_a_narrow_bpm_range() {
+        // the lags searched are rounded outwards, so they still take in the 120 BPM clicks
+        for bpm_range in [(118.0, 119.0), (119.0, 118.0)] {
+            let bpm = track(bpm_range).tempo().unwrap().bpm;
+            assert!((118.0..=119.0).contains(&bpm), "{bpm_range:?}: {bpm} BPM");
+        }
+    }
+
+    #[test]
+    fn extreme_bpm_ranges_are_searched_without_panicking() {
+        // upper limits past the envelope rate would put the shortest lag at 0, a lower one of 0 the longest at infinity
+        for bpm_range in [(60.0, 12000.0), (0.0, 200.0), (0.0, 0.0)] {
+            track(bpm_range);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::{Display, Formatter};
+use crate::fft::fft::{FrequencySpectrum, Hertz};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weighting {
+    Z,
+    A,
+    C,
+    Itu468,
+}
+
+impl Weighting {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Z => Self::A,
+            Self::A => Self::C,
+            Self::C => Self::Itu468,
+            Self::Itu468 => Self::Z,
+        }
+    }
+
+    // Gain of the weighting curve in dB, normalised to 0 dB at 1 kHz (or +12.2 dB at 6.3 kHz for 468)
+    pub fn gain_db(&self, hertz: Hertz) -> f32 {
+        let f = hertz.0 as f64;
+        let f2 = f * f;
+
+        let gain = match self {
+            Self::Z => return 0.0,
+            Self::A => {
+                let response = 12194f64.powi(2) * f2 * f2
+                    / ((f2 + 20.6f64.powi(2))
+                    * ((f2 + 107.7f64.powi(2)) * (f2 + 737.9f64.powi(2))).sqrt()
+                    * (f2 + 12194f64.powi(2)));
+                20.0 * response.log10() + 2.0
+            }
+            Self::C => {
+                let response = 12194f64.powi(2) * f2 / ((f2 + 20.6f64.powi(2)) * (f2 + 12194f64.powi(2)));
+                20.0 * response.log10() + 0.06
+            }
+            Self::Itu468 => {
+                let h1 = -4.737338981378384e-24 * f2.powi(3) + 2.043828333606125e-15 * f2 * f2
+                    - 1.363894795463638e-7 * f2 + 1.0;
+                let h2 = 1.306612257412824e-19 * f2 * f2 * f - 2.118150887518656e-11 * f2 * f
+                    + 5.559488023498642e-4 * f;
+                let response = 1.246332637532143e-4 * f / (h1 * h1 + h2 * h2).sqrt();
+                18.2 + 20.0 * response.log10()
+            }
+        };
+
+        gain as f32
+    }
+
+    // Linear amplitude gain
+    pub fn gain(&self, hertz: Hertz) -> f32 {
+        10f32.powf(self.gain_db(hertz) / 20.0)
+    }
+
+    pub fn apply(&self, spectrum: &FrequencySpectrum) -> FrequencySpectrum {
+        if *self == Self::Z {
+            return spectrum.clone();
+        }
+
+        spectrum.merge(spectrum, |_, (frequency, value), _| value * self.gain(frequency))
+    }
+}
+
+impl Display for Weighting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Z => "Z",
+            Self::A => "A",
+            Self::C => "C",
+            Self::Itu468 => "468"
+        })
+    }
+}
+
+// Display compensation rising by `slope` dB for every octave above the pivot (and falling below it)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tilt {
+    pub slope: f32,
+    pub pivot: Hertz,
+}
+
+impl Default for Tilt {
+    fn default() -> Self {
+        Self { slope: 0.0, pivot: Hertz(1000.0) }
+    }
+}
+
+impl Tilt {
+    pub fn gain_db(&self, hertz: Hertz) -> f32 {
+        if self.slope == 0.0 || hertz.0 <= 0.0 {
+            return 0.0;
+        }
+
+        self.slope * (hertz.0 / self.pivot.0).log2()
+    }
+}
+
+impl Display for Tilt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:+.1} dB/oct @ {:.0} Hz", self.slope, self.pivot.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fft::fft::Hertz;
+    use crate::fft::weighting::Weighting;
+
+    fn assert_gains(weighting: Weighting, table: &[(f32, f32)], tolerance: f32) {
+        for &(frequency, expected) in table {
+            let gain = weighting.gain_db(Hertz(frequency));
+            assert!((gain - expected).abs() <= tolerance, "{weighting} weighting at {frequency} Hz is {gain:.2} dB, expected {expected} dB");
+        }
+    }
+
+    // Values tabulated in IEC 61672-1, at nominal rather than exact frequencies
+    #[test]
+    fn a_weighting_matches_the_standard() {
+        assert_gains(Weighting::A, &[(31.5, -39.4), (100.0, -19.1), (1000.0, 0.0), (4000.0, 1.0), (10000.0, -2.5)], 0.2);
+    }
+
+    #[test]
+    fn c_weighting_matches_the_standard() {
+        assert_gains(Weighting::C, &[(31.5, -3.0), (100.0, -0.3), (1000.0, 0.0), (4000.0, -0.8), (10000.0, -4.4)], 0.2);
+    }
+
+    // Values tabulated in ITU-R BS.468-4
+    #[test]
+    fn itu_468_weighting_matches_the_standard() {
+        assert_gains(Weighting::Itu468, &[(31.5, -29.9), (100.0, -19.8), (1000.0, 0.0), (6300.0, 12.2), (10000.0, 8.1), (20000.0, -22.2)], 0.2);
+    }
+
+    #[test]
+    fn z_weighting_is_flat() {
+        assert_gains(Weighting::Z, &[(20.0, 0.0), (1000.0, 0.0), (20000.0, 0.0)], 0.0);
+    }
+}
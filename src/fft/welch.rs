@@ -0,0 +1,204 @@
+use std::fmt::{Display, Formatter};
+use realfft::{FftError, RealFftPlanner};
+use rustfft::num_complex::Complex;
+use crate::fft::fft::{AudioView, FrequencySpectrum, Hertz, SpectrumError};
+use crate::fft::scaling::Scaling;
+use crate::numtools::{hann, to_dbfs};
+
+// Welch's method: overlapping Hann windowed segments whose periodograms are averaged, trading frequency
+// resolution for a much steadier estimate of noise than a single frame gives.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Welch {
+    pub segment_size: usize,
+    // share of each segment that is also part of the next one, half is the usual choice for Hann windows
+    pub overlap: f32,
+}
+
+impl Default for Welch {
+    fn default() -> Self {
+        Self { segment_size: 4096, overlap: 0.5 }
+    }
+}
+
+impl Display for Welch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Welch ({} samples, {:.0} % overlap)", self.segment_size, self.overlap * 100.0)
+    }
+}
+
+impl Welch {
+    pub fn hop(&self) -> usize {
+        ((1.0 - self.overlap.clamp(0.0, 0.95)) * self.segment_size as f32).round().max(1.0) as usize
+    }
+
+    // Falls back to a single segment over everything when there are fewer samples than one segment holds
+    pub fn estimate(&self, samples: &[f32], sample_rate: u32, planner: &mut RealFftPlanner<f32>) -> Result<PowerSpectralDensity, FftError> {
+        let segment_size = self.segment_size.min(samples.len());
+        if segment_size < 2 {
+            return Ok(PowerSpectralDensity::default());
+        }
+
+        let mut window = vec![1.0; segment_size];
+        hann(&mut window, segment_size);
+
+        let fft = planner.plan_fft_forward(segment_size);
+        let mut input = vec![0.0; segment_size];
+        let mut output = vec![Complex::new(0.0, 0.0); segment_size / 2 + 1];
+        let mut sums = vec![0.0; output.len()];
+
+        let mut segments = 0;
+        for start in (0..=samples.len() - segment_size).step_by(self.hop()) {
+            for ((input, sample), weight) in input.iter_mut().zip(&samples[start..start + segment_size]).zip(&window) {
+                *input = sample * weight;
+            }
+
+            fft.process(&mut input, &mut output)?;
+            for (sum, bin) in sums.iter_mut().zip(&output) {
+                *sum += bin.norm_sqr();
+            }
+            segments += 1;
+        }
+
+        // |X|² / (Σw)² reads a sinusoid's power straight off its bin, dividing by the ENBW spreads that power into a density
+        let coherent = window.iter().sum::<f32>().powi(2);
+        let bandwidth = equivalent_noise_bandwidth(&window, sample_rate);
+        let last = sums.len() - 1;
+        let bins = sums.iter().enumerate().map(|(bin, sum)| {
+            // one-sided, DC and nyquist have no mirror image to fold in
+            let one_sided = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+            one_sided * sum / segments as f32 / coherent / bandwidth.0
+        }).collect();
+
+        Ok(PowerSpectralDensity { density: FrequencySpectrum::new(bins, sample_rate), segments, bandwidth })
+    }
+}
+
+// Width of the ideal rectangular filter passing as much noise as a bin of the window does, 1.5 bins for Hann
+pub fn equivalent_noise_bandwidth(window: &[f32], sample_rate: u32) -> Hertz {
+    let sum = window.iter().sum::<f32>();
+    let sum_of_squares = window.iter().map(|weight| weight * weight).sum::<f32>();
+    Hertz(sample_rate as f32 * sum_of_squares / (sum * sum))
+}
+
+// One-sided power spectral density, in squared full-scale units per Hz
+#[derive(Clone, Default)]
+pub struct PowerSpectralDensity {
+    // densities kept in a spectrum for its bin and frequency mapping
+    density: FrequencySpectrum,
+    pub segments: usize,
+    pub bandwidth: Hertz,
+}
+
+impl PowerSpectralDensity {
+    pub fn sample_rate(&self) -> u32 {
+        self.density.sample_rate()
+    }
+
+    pub fn len(&self) -> usize {
+        self.density.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(Hertz, f32)> + '_ {
+        self.density.iter()
+    }
+
+    // dB/Hz relative to a full-scale RMS of 1.0
+    pub fn level(&self, bin: usize) -> Option<f32> {
+        to_dbfs(self.density.get(bin)?.sqrt())
+    }
+
+    // As magnitudes like those of a single frame, so that the usual display and scalings apply, reading in dB/Hz with
+    // the power spectral density scaling
+    pub fn to_spectrum(&self) -> FrequencySpectrum {
+        let bins = self.density.iter().enumerate()
+            .map(|(bin, (_, density))| density.sqrt() / Scaling::PowerSpectralDensity.gain(&self.density, bin))
+            .collect();
+        FrequencySpectrum::new(bins, self.sample_rate())
+    }
+}
+
+// Estimates the density over the whole view, like `TryIntoFrequencySpectrum` but averaging many short segments
+pub trait TryIntoPowerSpectralDensity {
+    type Error;
+
+    fn try_into_psd(&self, planner: &mut RealFftPlanner<f32>, welch: Welch) -> Result<PowerSpectralDensity, Self::Error>;
+}
+
+impl TryIntoPowerSpectralDensity for AudioView {
+    type Error = SpectrumError;
+
+    fn try_into_psd(&self, planner: &mut RealFftPlanner<f32>, welch: Welch) -> Result<PowerSpectralDensity, Self::Error> {
+        Ok(welch.estimate(&self.samples()?, self.sample_rate(), planner)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use realfft::RealFftPlanner;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::audio::introspect::introspect;
+    use crate::fft::fft::Hertz;
+    use crate::fft::welch::{equivalent_noise_bandwidth, PowerSpectralDensity, TryIntoPowerSpectralDensity, Welch};
+    use crate::numtools::hann;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn samples(signal: Signal) -> Vec<f32> {
+        Generator::new(signal, SAMPLE_RATE).take(SAMPLE_RATE as usize).collect()
+    }
+
+    // Mean square of the signal, the density integrated over every bin
+    fn power(psd: &PowerSpectralDensity) -> f32 {
+        let bin_width = psd.sample_rate() as f32 / (2 * (psd.len() - 1)) as f32;
+        psd.iter().map(|(_, density)| density).sum::<f32>() * bin_width
+    }
+
+    #[test]
+    fn hann_bandwidth_is_one_and_a_half_bins() {
+        let mut window = vec![1.0; 4096];
+        hann(&mut window, 4096);
+        let bins = equivalent_noise_bandwidth(&window, SAMPLE_RATE).0 / (SAMPLE_RATE as f32 / 4096.0);
+        assert!((bins - 1.5).abs() < 0.01, "{bins} bins");
+    }
+
+    #[test]
+    fn averaging_segments_narrows_the_scatter_of_white_noise() {
+        let samples = samples(Signal::WhiteNoise { amplitude: 1.0 });
+        let levels = |segment_size| {
+            let psd = Welch { segment_size, overlap: 0.5 }.estimate(&samples, SAMPLE_RATE, &mut RealFftPlanner::new()).unwrap();
+            (1..psd.len() - 1).map(|bin| psd.level(bin).unwrap()).collect::<Vec<_>>()
+        };
+        let deviation = |levels: &[f32]| {
+            let mean = levels.iter().sum::<f32>() / levels.len() as f32;
+            (levels.iter().map(|level| (level - mean).powi(2)).sum::<f32>() / levels.len() as f32).sqrt()
+        };
+
+        // a single periodogram scatters its bins by about 5.6 dB, every averaged segment narrows that by √segments
+        let single = deviation(&levels(samples.len()));
+        let averaged = deviation(&levels(1024));
+        assert!(single > 4.0, "single segment scatters by {single} dB");
+        assert!(averaged < 1.0, "93 segments scatter by {averaged} dB");
+    }
+
+    #[test]
+    fn filling_buffer_reads_its_full_level() {
+        let (introspect, mut source) = introspect(Generator::new(Signal::Sine { frequency: Hertz(1000.0), amplitude: 0.5 }, SAMPLE_RATE), Duration::from_secs(1));
+        source.by_ref().take(SAMPLE_RATE as usize / 4).for_each(drop);
+        assert_eq!(introspect.filled(), SAMPLE_RATE as usize / 4);
+
+        let view = introspect.audio_views().remove(0).subview(0..introspect.filled());
+        let psd = view.try_into_psd(&mut RealFftPlanner::new(), Welch::default()).unwrap();
+        let power = power(&psd);
+        assert!((power / 0.125 - 1.0).abs() < 0.02, "mean square {power}");
+    }
+
+    #[test]
+    fn sine_power_integrates_to_its_mean_square() {
+        let samples = samples(Signal::Sine { frequency: Hertz(1000.0), amplitude: 0.5 });
+        let psd = Welch::default().estimate(&samples, SAMPLE_RATE, &mut RealFftPlanner::new()).unwrap();
+
+        let power = power(&psd);
+        assert!((power / 0.125 - 1.0).abs() < 0.02, "mean square {power}");
+    }
+}
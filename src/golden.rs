@@ -0,0 +1,142 @@
+// Golden image tests: synthetic signals go through the analysis and the software renderer, and the
+// result is compared with PNGs stored in tests/golden. A missing golden image fails the test, run with
+// UPDATE_GOLDEN=1 to write images for new cases or to overwrite them after an intended change to the
+// visuals. On a mismatch the rendered image is saved next to the golden one as <name>.actual.png for
+// comparison.
+
+use std::path::PathBuf;
+use std::time::Duration;
+use image::{Pixel, RgbaImage};
+use rodio::Source;
+use crate::audio::generator::{Generator, Signal};
+use crate::fft::fft::Hertz;
+use crate::fft::scale::FrequencyScale;
+use crate::numtools::from_dbfs;
+use crate::video::{FrameEncoder, render_video};
+use crate::viewport::Viewport;
+use crate::visualiser::{FrequencyAxis, plot_area, ViewMode, Visualiser};
+
+const SIZE: (u32, u32) = (640, 360);
+const SAMPLE_RATE: u32 = 48000;
+const FRAME_RATE: u32 = 30;
+
+// Long enough to fill the one second analysis buffer, with time left for the ballistics to settle
+const DURATION: f32 = 2.0;
+
+// A channel may be off by this much before the pixel counts as different
+const CHANNEL_TOLERANCE: u8 = 32;
+
+// Share of pixels allowed to differ, font rasterisation and antialiasing vary a little between platforms
+const PIXEL_TOLERANCE: f32 = 0.005;
+
+// Keeps the last frame it is given
+#[derive(Default)]
+struct LastFrame(Option<RgbaImage>);
+
+impl FrameEncoder for LastFrame {
+    fn encode(&mut self, frame: &RgbaImage) -> std::io::Result<()> {
+        self.0 = Some(frame.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn snapshot<C: FnOnce(&mut Visualiser)>(signal: Signal, configure: C) -> RgbaImage {
+    let mut visualiser = Visualiser::default();
+    configure(&mut visualiser);
+
+    let mut last_frame = LastFrame::default();
+    let source = Generator::new(signal, SAMPLE_RATE).take_duration(Duration::from_secs_f32(DURATION));
+    render_video(source, &mut visualiser, &mut last_frame, SIZE, FRAME_RATE).unwrap();
+    last_frame.0.expect("no frame was rendered")
+}
+
+fn assert_golden(name: &str, image: &RgbaImage) {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden");
+    let path = directory.join(format!("{name}.png"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(&directory).unwrap();
+        image.save(&path).unwrap();
+        eprintln!("wrote golden image {}", path.display());
+        return;
+    }
+
+    assert!(path.exists(), "{name}: no golden image at {}, run with UPDATE_GOLDEN=1 to create it", path.display());
+
+    let golden = image::open(&path).unwrap().to_rgba8();
+    assert_eq!(golden.dimensions(), image.dimensions(), "{name}: size differs from the golden image");
+
+    let different = golden.pixels().zip(image.pixels())
+        .filter(|(expected, actual)| expected.channels().iter().zip(actual.channels())
+            .any(|(expected, actual)| expected.abs_diff(*actual) > CHANNEL_TOLERANCE))
+        .count();
+    let share = different as f32 / (image.width() * image.height()) as f32;
+
+    if share > PIXEL_TOLERANCE {
+        let actual_path = directory.join(format!("{name}.actual.png"));
+        image.save(&actual_path).unwrap();
+        panic!("{name}: {:.2} % of pixels differ from the golden image, see {}", share * 100.0, actual_path.display());
+    }
+}
+
+// Level of the highest point of the live spectrum, read back from the white curve in the plot area
+fn drawn_peak(image: &RgbaImage) -> Option<f32> {
+    let (left, top, width, height) = plot_area(image.dimensions());
+    let level = Viewport::default().level;
+
+    let y = (top as u32..(top + height) as u32)
+        .find(|&y| (left as u32..(left + width) as u32).any(|x| image.get_pixel(x, y).0 == [u8::MAX; 4]))?;
+    Some(level.end() - (y as f32 - top) / height * (level.end() - level.start()))
+}
+
+#[test]
+fn spectrum_sine() {
+    let image = snapshot(Signal::Sine { frequency: Hertz(1000.0), amplitude: from_dbfs(-6.0) }, |_| {});
+    assert_golden("spectrum_sine", &image);
+
+    let peak = drawn_peak(&image).unwrap();
+    assert!((peak + 6.0).abs() < 1.0, "a -6 dBFS sine is drawn peaking at {peak} dBFS");
+}
+
+#[test]
+fn spectrum_sweep() {
+    assert_golden("spectrum_sweep", &snapshot(Signal::LogSweep { from: Hertz(20.0), to: Hertz(20000.0), duration: Duration::from_secs_f32(DURATION), amplitude: 0.5 }, |_| {}));
+}
+
+#[test]
+fn spectrum_white_noise() {
+    assert_golden("spectrum_white_noise", &snapshot(Signal::WhiteNoise { amplitude: 0.5 }, |_| {}));
+}
+
+#[test]
+fn spectrum_impulses() {
+    assert_golden("spectrum_impulses", &snapshot(Signal::ImpulseTrain { frequency: Hertz(100.0), amplitude: 1.0 }, |_| {}));
+}
+
+#[test]
+fn spectrum_peaks_mel_notes() {
+    let chord = Signal::MultiTone { tones: vec![(Hertz(220.0), 0.3), (Hertz(1760.0), 0.2)] };
+    assert_golden("spectrum_peaks_mel_notes", &snapshot(chord, |visualiser| {
+        visualiser.scale = FrequencyScale::Mel;
+        visualiser.axis = FrequencyAxis::Notes;
+        visualiser.show_peaks = true;
+    }));
+}
+
+#[test]
+fn bands_pink_noise() {
+    assert_golden("bands_pink_noise", &snapshot(Signal::PinkNoise { amplitude: 0.5 }, |visualiser| {
+        visualiser.view_mode = ViewMode::Bands;
+    }));
+}
+
+#[test]
+fn chromagram_sine() {
+    assert_golden("chromagram_sine", &snapshot(Signal::Sine { frequency: Hertz(440.0), amplitude: 0.5 }, |visualiser| {
+        visualiser.view_mode = ViewMode::Chromagram;
+    }));
+}
@@ -3,51 +3,88 @@
 #![feature(let_chains)]
 
 mod audio;
+mod cli;
 mod numtools;
 mod fft;
+#[cfg(test)]
+mod golden;
+mod renderer;
+mod ticks;
+mod video;
+mod viewport;
+mod visualiser;
 
 use std::any::Any;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
-use std::time::Duration;
-use imageproc::drawing::{draw_antialiased_line_segment_mut, draw_text_mut};
-use image::{Rgba, RgbaImage};
-use imageproc::pixelops::interpolate;
-use itertools::{EitherOrBoth, Itertools};
-use nannou::lyon::geom::euclid::approxeq::ApproxEq;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use nannou::prelude::*;
 use nannou::wgpu::{Device, Texture};
-use nannou::winit::event::VirtualKeyCode;
-use once_cell::sync;
-use realfft::RealFftPlanner;
+use nannou::winit::event::{MouseScrollDelta, TouchPhase, VirtualKeyCode};
 use rodio::{cpal, Decoder, DeviceTrait, OutputStream, Sink, Source};
 use rodio::cpal::traits::HostTrait;
-use rusttype::{Font, Scale};
+use crate::audio::generator::{Generator, Signal};
 use crate::audio::introspect::{introspect, introspect_device, Introspectable};
-use crate::fft::fft::{FrequencySpectrum, Hertz, TryIntoFrequencySpectrum};
-use crate::numtools::{lerp, lerp_index_fn, to_dbfs};
+use crate::fft::export::{export_spectra, ExportFormat};
+use crate::fft::fft::Hertz;
+use crate::fft::reference::Reference;
+use crate::fft::scaling::{Calibration, CALIBRATOR_LEVEL, Scaling};
+use crate::fft::welch::Welch;
+use crate::renderer::NannouRenderer;
+use crate::viewport::Preset;
+use crate::visualiser::{FrequencyAxis, Visualiser, ViewMode};
 
 fn main() {
+    let arguments = std::env::args().skip(1).collect::<Vec<_>>();
+    if !arguments.is_empty() {
+        if let Err(error) = cli::run(&arguments) {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     nannou::app(model)
         .update(update)
         .loop_mode(LoopMode::RefreshSync)
         .run();
 }
+
 struct Model {
     introspect: Introspectable<f32>,
-    planner: RealFftPlanner<f32>,
-    spectra: Vec<FrequencySpectrum>,
+    visualiser: Visualiser,
     visualiser_texture: Texture,
 
     is_input: bool,
     device: Box<rodio::Device>,
     _stream: Option<Box<dyn Any>>,
     sink: Option<Sink>,
+
+    transparent_screenshots: bool,
+    // draw on the CPU and upload the result as a texture, rather than drawing with nannou
+    software_rendering: bool,
+
+    // last cursor position while dragging the plot around
+    drag: Option<(f32, f32)>,
+
+    // index into Signal::presets of the test signal playing, if any
+    signal: Option<usize>,
 }
 
-static FONT_BYTES: &[u8] = include_bytes!("liberation.ttf");
-static FONT: sync::Lazy<Font<'_>> = sync::Lazy::new(|| Font::try_from_bytes(FONT_BYTES).unwrap());
+// Where the reference trace is saved, loaded by dropping the file back onto the window
+const REFERENCE_PATH: &str = "reference.ref";
+
+// Where calibrating saves the dB SPL offset, loaded by dropping the file back onto the window
+const CALIBRATION_PATH: &str = "calibration.cal";
+
+// Resolution multiplier for screenshots taken with shift held
+const SCREENSHOT_SCALE: u32 = 2;
+
+// Change in visible span for every line scrolled
+const ZOOM_STEP: f32 = 0.85;
+
+const GENERATOR_SAMPLE_RATE: u32 = 48000;
 
 fn model(app: &App) -> Model {
     app.new_window()
@@ -55,6 +92,10 @@ fn model(app: &App) -> Model {
         .view(view)
         .key_pressed(on_key_pressed)
         .dropped_file(on_dropped_file)
+        .mouse_wheel(on_mouse_wheel)
+        .mouse_pressed(on_mouse_pressed)
+        .mouse_released(on_mouse_released)
+        .mouse_moved(on_mouse_moved)
         .build()
         .unwrap();
 
@@ -72,15 +113,19 @@ fn model(app: &App) -> Model {
     Model {
         _stream: Some(Box::new(_stream)), sink: Some(sink),
         introspect: Introspectable::default(),
-        planner: RealFftPlanner::new(),
-        spectra: Vec::new(),
+        visualiser: Visualiser::default(),
         visualiser_texture: texture.into(),
         device: Box::new(device),
         is_input: false,
+
+        transparent_screenshots: false,
+        software_rendering: true,
+        drag: None,
+        signal: None,
     }
 }
 
-fn on_key_pressed(_app: &App, model: &mut Model, key: Key) {
+fn on_key_pressed(app: &App, model: &mut Model, key: Key) {
     match key {
         VirtualKeyCode::P => {
             if let Some(sink) = &model.sink {
@@ -111,10 +156,173 @@ fn on_key_pressed(_app: &App, model: &mut Model, key: Key) {
 
             eprintln!("{:?}", model.device.name());
         }
+        VirtualKeyCode::Tab => {
+            let presets = Signal::presets();
+            let index = model.signal.map_or(0, |index| (index + 1) % presets.len());
+            eprintln!("playing {}", presets[index]);
+            play(model, Generator::new(presets[index].clone(), GENERATOR_SAMPLE_RATE));
+            model.signal = Some(index);
+        }
+        VirtualKeyCode::N => {
+            model.visualiser.axis = match model.visualiser.axis {
+                FrequencyAxis::Hertz => FrequencyAxis::Notes,
+                FrequencyAxis::Notes => FrequencyAxis::Hertz,
+            };
+        }
+        VirtualKeyCode::M => {
+            model.visualiser.scale = model.visualiser.scale.next();
+        }
+        VirtualKeyCode::K => {
+            model.visualiser.show_keyboard ^= true;
+        }
+        VirtualKeyCode::T => {
+            model.visualiser.show_tuner ^= true;
+        }
+        VirtualKeyCode::B => {
+            model.visualiser.show_beats ^= true;
+        }
+        VirtualKeyCode::L => {
+            model.visualiser.show_peaks ^= true;
+        }
+        VirtualKeyCode::A => {
+            model.visualiser.show_features ^= true;
+        }
+        VirtualKeyCode::X => {
+            model.visualiser.show_crosshair ^= true;
+        }
+        VirtualKeyCode::V => {
+            model.visualiser.view_mode = match model.visualiser.view_mode {
+                ViewMode::Spectrum => ViewMode::Bands,
+                ViewMode::Bands => ViewMode::Chromagram,
+                ViewMode::Chromagram => ViewMode::Spectrum,
+            };
+        }
+        VirtualKeyCode::W => {
+            model.visualiser.weighting = model.visualiser.weighting.next();
+        }
+        VirtualKeyCode::Comma => {
+            model.visualiser.tilt.slope -= 0.5;
+        }
+        VirtualKeyCode::Period => {
+            model.visualiser.tilt.slope += 0.5;
+        }
+        VirtualKeyCode::Minus => {
+            model.visualiser.tilt.pivot = Hertz((model.visualiser.tilt.pivot.0 / 2.0).max(20.0));
+        }
+        VirtualKeyCode::Equals => {
+            model.visualiser.tilt.pivot = Hertz((model.visualiser.tilt.pivot.0 * 2.0).min(20000.0));
+        }
+        VirtualKeyCode::Key1 | VirtualKeyCode::Key2 | VirtualKeyCode::Key3
+        | VirtualKeyCode::Key4 | VirtualKeyCode::Key5 | VirtualKeyCode::Key6 => {
+            let index = key as usize - VirtualKeyCode::Key1 as usize;
+            if let Some(trace) = model.visualiser.traces.get_mut(index) {
+                trace.visible ^= true;
+                trace.averager.reset();
+            }
+        }
+        VirtualKeyCode::R => {
+            model.visualiser.traces.iter_mut().for_each(|trace| trace.averager.reset());
+        }
+        VirtualKeyCode::F => {
+            // freeze the first visible averaged trace if there is one, the live spectrum otherwise
+            let spectra = model.visualiser.traces.iter().find(|trace| trace.visible)
+                .map_or(&model.visualiser.spectra[..], |trace| trace.averager.spectra());
+            model.visualiser.reference = Some(Reference::capture(spectra));
+        }
+        VirtualKeyCode::S => {
+            if let Some(reference) = &model.visualiser.reference {
+                if let Err(error) = reference.save(Path::new(REFERENCE_PATH)) {
+                    eprintln!("failed to save reference: {error}");
+                }
+            }
+        }
+        VirtualKeyCode::E | VirtualKeyCode::J => {
+            let format = if key == VirtualKeyCode::E { ExportFormat::Csv } else { ExportFormat::Json };
+            let path = timestamped_path("spectrum", format.extension());
+            match export_spectra(&path, &model.visualiser.spectra, format, model.visualiser.scaling) {
+                Ok(()) => eprintln!("exported spectrum to {}", path.display()),
+                Err(error) => eprintln!("failed to export spectrum: {error}")
+            }
+        }
+        VirtualKeyCode::F12 => {
+            let scale = if app.keys.mods.shift() { SCREENSHOT_SCALE } else { 1 };
+            let [width, height] = model.visualiser_texture.size();
+            let mut image = model.visualiser.render((width * scale, height * scale), None, scale as f32);
+
+            if !model.transparent_screenshots {
+                image.pixels_mut().for_each(|pixel| pixel[3] = u8::MAX);
+            }
+
+            let path = timestamped_path("screenshot", "png");
+            match image.save(&path) {
+                Ok(()) => eprintln!("saved screenshot to {}", path.display()),
+                Err(error) => eprintln!("failed to save screenshot: {error}")
+            }
+        }
+        VirtualKeyCode::F11 => {
+            model.transparent_screenshots ^= true;
+        }
+        VirtualKeyCode::H => {
+            model.software_rendering ^= true;
+        }
+        VirtualKeyCode::D => {
+            model.visualiser.show_difference ^= true;
+        }
+        VirtualKeyCode::Back => {
+            model.visualiser.reference = None;
+        }
+        VirtualKeyCode::O => {
+            model.visualiser.octave_fraction = model.visualiser.octave_fraction.next();
+        }
+        VirtualKeyCode::F1 | VirtualKeyCode::F2 | VirtualKeyCode::F3 => {
+            let preset = match key {
+                VirtualKeyCode::F1 => Preset::Full,
+                VirtualKeyCode::F2 => Preset::Bass,
+                _ => Preset::Vocals,
+            };
+            model.visualiser.viewport.preset(preset);
+        }
+        VirtualKeyCode::Q => {
+            model.visualiser.scaling = model.visualiser.scaling.next();
+        }
+        VirtualKeyCode::U => {
+            // densities are only meaningful in dB/Hz
+            model.visualiser.welch = match model.visualiser.welch {
+                Some(_) => None,
+                None => {
+                    model.visualiser.scaling = Scaling::PowerSpectralDensity;
+                    Some(Welch::default())
+                }
+            };
+        }
+        VirtualKeyCode::Z => {
+            if app.keys.mods.shift() {
+                model.visualiser.calibration = None;
+            } else if let Some(calibration) = model.visualiser.calibrate(CALIBRATOR_LEVEL) {
+                eprintln!("calibrated to {CALIBRATOR_LEVEL} dB SPL, offset {:.1} dB", calibration.offset);
+                if let Err(error) = calibration.save(Path::new(CALIBRATION_PATH)) {
+                    eprintln!("failed to save calibration: {error}");
+                }
+            }
+        }
+        VirtualKeyCode::G => {
+            model.visualiser.viewport.auto_level ^= true;
+        }
+        VirtualKeyCode::LBracket => {
+            model.visualiser.tuning = Hertz((model.visualiser.tuning.0 - 1.0).max(1.0));
+        }
+        VirtualKeyCode::RBracket => {
+            model.visualiser.tuning = Hertz(model.visualiser.tuning.0 + 1.0);
+        }
         _ => {}
     }
 }
 
+fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    PathBuf::from(format!("{prefix}-{timestamp}.{extension}"))
+}
+
 fn init_device(model: &mut Model) -> Option<()> {
     let device = &model.device;
 
@@ -150,27 +358,89 @@ fn init_device(model: &mut Model) -> Option<()> {
 }
 
 fn on_dropped_file(_app: &App, model: &mut Model, file: PathBuf) {
-    let _: Option<()> = try {
-        if model.is_input {
-            model.is_input = false;
-            init_device(model);
+    if file.extension().map_or(false, |extension| extension == "ref") {
+        match Reference::load(&file) {
+            Ok(reference) => model.visualiser.reference = Some(reference),
+            Err(error) => eprintln!("failed to load reference: {error}")
         }
+        return;
+    }
 
-        if let Some(sink) = &model.sink {
-            let file = BufReader::new(File::open(file).ok()?);
-            let source = Decoder::new(file).ok()?.convert_samples::<f32>();
+    if file.extension().map_or(false, |extension| extension == "cal") {
+        match Calibration::load(&file) {
+            Ok(calibration) => model.visualiser.calibration = Some(calibration),
+            Err(error) => eprintln!("failed to load calibration: {error}")
+        }
+        return;
+    }
 
-            let (introspect, introspected) = introspect(source, Duration::from_millis(1000));
+    let _: Option<()> = try {
+        let file = BufReader::new(File::open(file).ok()?);
+        let source = Decoder::new(file).ok()?.convert_samples::<f32>();
+        play(model, source);
+        model.signal = None;
+    };
+}
 
-            if !sink.empty() {
-                sink.clear();
-            }
+// Switches to playback if listening to an input, then replaces whatever is playing with the source
+fn play<S: Source<Item=f32> + Send + 'static>(model: &mut Model, source: S) {
+    if model.is_input {
+        model.is_input = false;
+        init_device(model);
+    }
+
+    if let Some(sink) = &model.sink {
+        let (introspect, introspected) = introspect(source, Duration::from_millis(1000));
 
-            model.introspect = introspect;
-            sink.append(introspected);
-            sink.play();
+        if !sink.empty() {
+            sink.clear();
         }
+
+        model.introspect = introspect;
+        model.visualiser.reset_analysis();
+        sink.append(introspected);
+        sink.play();
+    }
+}
+
+fn on_mouse_wheel(app: &App, model: &mut Model, delta: MouseScrollDelta, _phase: TouchPhase) {
+    let lines = match delta {
+        MouseScrollDelta::LineDelta(_, y) => y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
     };
+
+    // scrolling up zooms in, hold shift to zoom the dB axis instead of the frequency axis
+    let [width, height] = model.visualiser_texture.size();
+    model.visualiser.zoom((width, height), cursor(app, model), ZOOM_STEP.powf(lines), app.keys.mods.shift());
+}
+
+fn on_mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    if button == MouseButton::Left {
+        model.drag = Some(cursor(app, model));
+    }
+}
+
+fn on_mouse_released(_app: &App, model: &mut Model, button: MouseButton) {
+    if button == MouseButton::Left {
+        model.drag = None;
+    }
+}
+
+fn on_mouse_moved(app: &App, model: &mut Model, _position: Point2) {
+    if let Some((last_x, last_y)) = model.drag {
+        let (x, y) = cursor(app, model);
+        let [width, height] = model.visualiser_texture.size();
+        model.visualiser.pan((width, height), (x - last_x, y - last_y));
+        model.drag = Some((x, y));
+    }
+}
+
+// Mouse position in image coordinates.
+// nannou puts the origin in the middle of the window with y pointing up, images have it in the top left
+fn cursor(app: &App, model: &Model) -> (f32, f32) {
+    let [width, height] = model.visualiser_texture.size();
+    let mouse = app.mouse.position();
+    (mouse.x + width as f32 / 2.0, height as f32 / 2.0 - mouse.y)
 }
 
 fn on_resize(app: &App, model: &mut Model, win: Vec2) {
@@ -187,124 +457,30 @@ fn build_texture(device: &Device, size: Vec2) -> Texture {
 
 fn update(_app: &App, model: &mut Model, update: Update) {
     let delta_time = update.since_last.as_secs_f32();
-
-    // Smoothing over time, specified in percentage change in (0.01s)
-    // For example, 0.2 here means that the spectrum moves 20 % closer to new values every 0.01 seconds
-    let lerp_per_cs = 0.2f32;
-
-    // FFT size for the low frequency resolution, high time resolution transform
-    const LOW_RES_FFT_SIZE: usize = 8192;
-
-    model.spectra = model.spectra.iter().cloned().zip_longest(
-        model.introspect.audio_views().iter().map(|view| {
-            let lo_res_spectrum = view.subview(0..LOW_RES_FFT_SIZE).try_into_spectrum(&mut model.planner).unwrap();
-            let hi_res_spectrum = view.try_into_spectrum(&mut model.planner).unwrap();
-
-            hi_res_spectrum.merge(&lo_res_spectrum, |_, (frequency, value), other| {
-                let approximate_bin = other.hertz_to_bin(frequency);
-                lerp(value, lerp_index_fn(|x| other.get(x), approximate_bin, 0f32), 0.6f32)
-            })
-        })
-    ).map(|eob| {
-        match eob {
-            EitherOrBoth::Both(old, new) => new.merge(&old, |_, (frequency, value), old| {
-                let approximate_bin = old.hertz_to_bin(frequency);
-                let old_value = lerp_index_fn(|x| old.get(x), approximate_bin, 0f32);
-
-                lerp(old_value, value, 1.0 - (1.0 - lerp_per_cs).pow(delta_time * 100.0))
-            }),
-            EitherOrBoth::Left(_) => FrequencySpectrum::default(),
-            EitherOrBoth::Right(new) => new
-        }
-    }).collect();
+    model.visualiser.update(&model.introspect, delta_time);
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
     frame.clear(BLACK);
 
     let texture_size = model.visualiser_texture.size();
-    let mut image = RgbaImage::new(texture_size[0], texture_size[1]);
-
-    const MIN_DBFS: f32 = -120.0;
-    const MAX_HEIGHT: f32 = 1.0;
-
-    const MARGIN: f32 = 0.05;
-
-    let margin_y = MARGIN * image.height() as f32;
-    let margin_x = margin_y;
-
-    let drawing_area_width = (image.width() as f32 - margin_x * 2.0) as usize;
-    let drawing_area_height = (image.height() as f32 - margin_y * 2.0) as usize;
-
-    let dbfs_to_y = |amplitude| {
-        margin_y + map_range(map_range(amplitude, MIN_DBFS, 0.0, 0f32, 1f32)
-                      .clamp(0.0, 1.0), 0.0, 1.0, drawing_area_height as f32, drawing_area_height as f32 * (1.0 - MAX_HEIGHT))
-    };
-
-
-
-    for spectrum in &model.spectra {
-        let octaves: f32 = (spectrum.nyquist_frequency() as f32 / 10.0).log2();
-
-        let min = margin_x;
-        let max = drawing_area_width as f32;
-        let step = drawing_area_width as f32 / octaves;
-
-        let mut px = None;
-        let mut py = None;
-
-        for x in (margin_x as usize)..(margin_x as usize + drawing_area_width) {
-            let zero_aligned_x = x as f32 - min;
-            let hertz_target = Hertz(map_range(2.0f32.pow((zero_aligned_x - max) / step), 0f32, 1.0, 0f32, spectrum.nyquist_frequency() as f32));
-
-            let index = spectrum.hertz_to_bin(hertz_target);
-            let sample = lerp_index_fn(|index| { spectrum.get(index) }, index, 0.0);
-
-            // skip if NaN, inf, or some other nasty number
-            let Some(amplitude) = to_dbfs(sample) else {
-                continue;
-            };
-
-            let y = dbfs_to_y(amplitude);
-
-            draw_antialiased_line_segment_mut(&mut image,
-                                              (px.unwrap_or(x) as i32, py.unwrap_or(y) as i32),
-                                              (x as i32, y as i32),
-                                              Rgba([u8::MAX; 4]), interpolate);
-
-            px = Some(x);
-            py = Some(y);
-        }
+    let draw = app.draw();
 
-        let image_height = image.height() as i32;
-        for hertz in (-4i32..6).map(|x| 261.626f32 * 2f32.pow(x as f32)).chain([0f32, spectrum.nyquist_frequency() as f32]) {
-            let ratio = hertz / (spectrum.nyquist_frequency() as f32);
-            let x = if ratio.approx_eq(&0.0) { min } else { ((max + min) * 2.0.ln() + step * ratio.ln()) / (2.0.ln()) };
+    if model.software_rendering {
+        let image = model.visualiser.render((texture_size[0], texture_size[1]), Some(cursor(app, model)), 1.0);
 
-            draw_text_mut(&mut image, Rgba([u8::MAX; 4]),
-                          x as i32, image_height - (margin_y * 0.9) as i32,
-                          Scale::uniform(16.0), &FONT,
-                          &*format!("{hertz:.0}"));
-        }
-    }
+        let flat_samples = image.as_flat_samples();
+        model.visualiser_texture.upload_data(
+            app.main_window().device(),
+            &mut *frame.command_encoder(),
+            &flat_samples.as_slice(),
+        );
 
-    const DBFS_TICK_COUNT: usize = 8;
-    for amplitude in (0..=DBFS_TICK_COUNT).map(|index| index as f32 / DBFS_TICK_COUNT as f32 * MIN_DBFS) {
-        let y = dbfs_to_y(amplitude);
-        draw_text_mut(&mut image, Rgba([u8::MAX; 4]),
-                      margin_x as i32 / 10, y as i32,
-                      Scale::uniform(16.0), &FONT,
-                      &*format!("{amplitude:.0}"));
+        draw.texture(&model.visualiser_texture);
+    } else {
+        let mut renderer = NannouRenderer::new(&draw, (texture_size[0], texture_size[1]));
+        model.visualiser.draw(&mut renderer, Some(cursor(app, model)), 1.0);
     }
 
-    let flat_samples = image.as_flat_samples();
-    model.visualiser_texture.upload_data(
-        app.main_window().device(),
-        &mut *frame.command_encoder(),
-        &flat_samples.as_slice(),
-    );
-
-    let draw = app.draw();
-    draw.texture(&model.visualiser_texture);
     draw.to_frame(app, &frame).unwrap();
-}
\ No newline at end of file
+}
@@ -34,4 +34,31 @@ where
     C: Mul<f32, Output=D>
 {
     a + (b - a) * t
-}
\ No newline at end of file
+}
+
+// Offset of the vertex of the parabola through three equally spaced points, relative to the middle one
+pub fn parabolic_offset(left: f32, centre: f32, right: f32) -> f32 {
+    let denominator = left - 2.0 * centre + right;
+    if denominator.abs() <= f32::EPSILON {
+        0.0
+    } else {
+        (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+    }
+}
+// Smallest 1, 2 or 5 times a power of ten that splits `span` into at most `max_count` steps
+pub fn nice_step(span: f32, max_count: usize) -> f32 {
+    let rough = span.abs() / max_count.max(1) as f32;
+    if rough <= 0.0 || !rough.is_finite() {
+        return 1.0;
+    }
+
+    let magnitude = 10f32.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|multiple| multiple * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude)
+}
+
+// Linear amplitude of a level in dBFS, the inverse of `to_dbfs`
+pub fn from_dbfs(level: f32) -> f32 {
+    10f32.powf(level / 20.0)
+}
@@ -0,0 +1,129 @@
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::{draw_antialiased_line_segment_mut, draw_filled_rect_mut, draw_text_mut, text_size};
+use imageproc::pixelops::interpolate;
+use imageproc::rect::Rect;
+use nannou::color::rgba8;
+use nannou::geom::pt2;
+use nannou::Draw;
+use once_cell::sync;
+use rusttype::{Font, Scale};
+
+static FONT_BYTES: &[u8] = include_bytes!("liberation.ttf");
+static FONT: sync::Lazy<Font<'_>> = sync::Lazy::new(|| Font::try_from_bytes(FONT_BYTES).unwrap());
+
+// The drawing operations the visualiser is made of. Coordinates are in pixels from the top left corner,
+// like images, whatever the backend does underneath.
+pub trait Renderer {
+    fn size(&self) -> (u32, u32);
+
+    fn fill(&mut self, colour: Rgba<u8>);
+
+    fn line(&mut self, from: (i32, i32), to: (i32, i32), colour: Rgba<u8>);
+
+    fn rect(&mut self, rect: Rect, colour: Rgba<u8>);
+
+    // `position` is the top left corner of the text, `size` its height in pixels
+    fn text(&mut self, position: (i32, i32), size: f32, colour: Rgba<u8>, text: &str);
+
+    fn text_size(&self, size: f32, text: &str) -> (i32, i32) {
+        text_size(Scale::uniform(size), &FONT, text)
+    }
+}
+
+// Draws into an image on the CPU, needs neither a GPU nor a window
+pub struct SoftwareRenderer {
+    image: RgbaImage,
+}
+
+impl SoftwareRenderer {
+    pub fn new((width, height): (u32, u32)) -> Self {
+        Self { image: RgbaImage::new(width, height) }
+    }
+
+    pub fn into_image(self) -> RgbaImage {
+        self.image
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn size(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn fill(&mut self, colour: Rgba<u8>) {
+        self.image.pixels_mut().for_each(|pixel| *pixel = colour);
+    }
+
+    fn line(&mut self, from: (i32, i32), to: (i32, i32), colour: Rgba<u8>) {
+        draw_antialiased_line_segment_mut(&mut self.image, from, to, colour, interpolate);
+    }
+
+    fn rect(&mut self, rect: Rect, colour: Rgba<u8>) {
+        draw_filled_rect_mut(&mut self.image, rect, colour);
+    }
+
+    fn text(&mut self, (x, y): (i32, i32), size: f32, colour: Rgba<u8>, text: &str) {
+        draw_text_mut(&mut self.image, colour, x, y, Scale::uniform(size), &FONT, text);
+    }
+}
+
+static NANNOU_FONT: sync::Lazy<nannou::text::Font> = sync::Lazy::new(|| nannou::text::Font::try_from_bytes(FONT_BYTES).unwrap());
+
+// Queues everything onto a nannou `Draw`, which renders it on the GPU
+pub struct NannouRenderer<'a> {
+    draw: &'a Draw,
+    size: (u32, u32),
+}
+
+impl<'a> NannouRenderer<'a> {
+    pub fn new(draw: &'a Draw, size: (u32, u32)) -> Self {
+        Self { draw, size }
+    }
+
+    // nannou puts the origin in the middle of the window with y pointing up
+    fn point(&self, x: f32, y: f32) -> nannou::geom::Point2 {
+        pt2(x - self.size.0 as f32 / 2.0, self.size.1 as f32 / 2.0 - y)
+    }
+}
+
+impl Renderer for NannouRenderer<'_> {
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn fill(&mut self, colour: Rgba<u8>) {
+        self.draw.rect()
+            .w_h(self.size.0 as f32, self.size.1 as f32)
+            .color(rgba8(colour[0], colour[1], colour[2], colour[3]));
+    }
+
+    fn line(&mut self, (x1, y1): (i32, i32), (x2, y2): (i32, i32), colour: Rgba<u8>) {
+        self.draw.line()
+            .start(self.point(x1 as f32 + 0.5, y1 as f32 + 0.5))
+            .end(self.point(x2 as f32 + 0.5, y2 as f32 + 0.5))
+            .weight(1.0)
+            .color(rgba8(colour[0], colour[1], colour[2], colour[3]));
+    }
+
+    fn rect(&mut self, rect: Rect, colour: Rgba<u8>) {
+        let (width, height) = (rect.width() as f32, rect.height() as f32);
+        self.draw.rect()
+            .xy(self.point(rect.left() as f32 + width / 2.0, rect.top() as f32 + height / 2.0))
+            .w_h(width, height)
+            .color(rgba8(colour[0], colour[1], colour[2], colour[3]));
+    }
+
+    fn text(&mut self, (x, y): (i32, i32), size: f32, colour: Rgba<u8>, text: &str) {
+        // nannou lays text out inside a box, so give it a box exactly as large as the text
+        let (width, height) = self.text_size(size, text);
+        self.draw.text(text)
+            .font(&NANNOU_FONT)
+            .font_size(size as u32)
+            .no_line_wrap()
+            .left_justify()
+            .align_text_top()
+            .xy(self.point(x as f32 + width as f32 / 2.0, y as f32 + height as f32 / 2.0))
+            .w_h(width as f32 + 1.0, height as f32)
+            .color(rgba8(colour[0], colour[1], colour[2], colour[3]));
+    }
+}
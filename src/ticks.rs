@@ -0,0 +1,98 @@
+use std::ops::RangeInclusive;
+use crate::fft::fft::Hertz;
+use crate::fft::scale::FrequencyScale;
+use crate::numtools::nice_step;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tick {
+    pub value: f32,
+    pub label: String,
+    // powers of ten, which get a brighter gridline
+    pub major: bool,
+}
+
+impl Tick {
+    pub fn new(value: f32, label: String) -> Self {
+        Self { value, label, major: false }
+    }
+}
+
+// Keeps ticks from the coarsest level to the finest, skipping any that would land closer than
+// `min_spacing` pixels to one already kept, so that finer ticks only fill the gaps that have room
+pub fn spaced<L, P>(levels: L, position: P, min_spacing: f32) -> Vec<Tick>
+where
+    L: IntoIterator<Item=Vec<Tick>>,
+    P: Fn(f32) -> f32
+{
+    let mut kept: Vec<(f32, Tick)> = Vec::new();
+    for level in levels {
+        for tick in level {
+            let x = position(tick.value);
+            if x.is_finite() && kept.iter().all(|(other, _)| (other - x).abs() >= min_spacing) {
+                kept.push((x, tick));
+            }
+        }
+    }
+
+    kept.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    kept.into_iter().map(|(_, tick)| tick).collect()
+}
+
+// Every multiple of `step` within the range
+fn multiples(range: &RangeInclusive<f32>, step: f32) -> impl Iterator<Item=f32> {
+    let first = (range.start() / step).ceil() as i64;
+    let last = (range.end() / step).floor() as i64;
+    (first..=last).map(move |index| index as f32 * step)
+}
+
+// Ticks for a frequency axis `length` pixels long. Nonlinear scales get 1, 2 and 5 times every power of ten,
+// falling back on evenly spaced round numbers when zoomed in too far for those, and linear scales only get the latter.
+pub fn frequency_ticks(scale: FrequencyScale, range: &RangeInclusive<f32>, length: f32, min_spacing: f32) -> Vec<Tick> {
+    let position = |hertz: f32| scale.normalise(Hertz(hertz), range) * length;
+
+    let decades = |multipliers: &[f32]| {
+        let lowest = range.start().max(10.0).log10().floor() as i32;
+        let highest = range.end().max(10.0).log10().ceil() as i32;
+        (lowest..=highest).flat_map(|exponent| multipliers.iter().map(move |multiplier| (*multiplier, multiplier * 10f32.powi(exponent))))
+            .filter(|(_, hertz)| range.contains(hertz))
+            .map(|(multiplier, hertz)| Tick { value: hertz, label: format_frequency(hertz), major: multiplier == 1.0 })
+            .collect::<Vec<_>>()
+    };
+
+    let mut levels = Vec::new();
+    if scale != FrequencyScale::Linear {
+        // 0 Hz sits at the left edge of the scales that reach it
+        levels.push(range.contains(&0.0).then(|| Tick { value: 0.0, label: "0".to_string(), major: true }).into_iter().collect());
+        levels.push(decades(&[1.0]));
+        levels.push(decades(&[2.0, 5.0]));
+    }
+
+    if levels.iter().map(Vec::len).sum::<usize>() < 3 {
+        let step = nice_step(range.end() - range.start(), (length / min_spacing).max(1.0) as usize);
+        levels.push(multiples(range, step).map(|hertz| Tick::new(hertz, format_frequency(hertz))).collect());
+    }
+
+    spaced(levels, position, min_spacing)
+}
+
+// Evenly spaced round dB values, as many as fit in `length` pixels
+pub fn level_ticks(range: &RangeInclusive<f32>, length: f32, min_spacing: f32) -> Vec<Tick> {
+    let step = nice_step(range.end() - range.start(), (length / min_spacing).max(1.0) as usize);
+    multiples(range, step).map(|level| Tick::new(level, trim(level))).collect()
+}
+
+// Short labels, "500", "1k", "12.5k"
+pub fn format_frequency(hertz: f32) -> String {
+    if hertz >= 1000.0 {
+        format!("{}k", trim(hertz / 1000.0))
+    } else {
+        trim(hertz)
+    }
+}
+
+// Up to three decimals, without trailing zeros
+fn trim(value: f32) -> String {
+    let text = format!("{value:.3}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0".to_string() } else { text.to_string() }
+}
@@ -0,0 +1,169 @@
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use image::RgbaImage;
+use rodio::Source;
+use crate::audio::introspect::introspect;
+use crate::visualiser::Visualiser;
+
+// Consumes rendered frames, one after another, all of the same size
+pub trait FrameEncoder {
+    fn encode(&mut self, frame: &RgbaImage) -> std::io::Result<()>;
+
+    fn finish(&mut self) -> std::io::Result<()>;
+}
+
+// Pipes raw RGBA frames into ffmpeg, which muxes them with the audio of the original file
+pub struct FfmpegEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    // collects what ffmpeg reports, read on a thread so that a chatty ffmpeg can't block on a full pipe
+    stderr: Option<JoinHandle<String>>,
+    size: (u32, u32),
+}
+
+impl FfmpegEncoder {
+    pub fn spawn(output: &Path, audio: &Path, (width, height): (u32, u32), frame_rate: u32) -> std::io::Result<Self> {
+        let webm = output.extension().is_some_and(|extension| extension == "webm");
+        let (video_codec, audio_codec) = if webm { ("libvpx-vp9", "libopus") } else { ("libx264", "aac") };
+
+        let mut child = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pixel_format", "rgba"])
+            .args(["-video_size", &format!("{width}x{height}"), "-framerate", &frame_rate.to_string()])
+            .args(["-i", "-"])
+            .arg("-i").arg(audio)
+            .args(["-map", "0:v:0", "-map", "1:a:0"])
+            .args(["-c:v", video_codec, "-pix_fmt", "yuv420p", "-c:a", audio_codec, "-shortest"])
+            .arg(output)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                ErrorKind::NotFound => Error::new(ErrorKind::NotFound, "ffmpeg was not found, is it installed and on the PATH?"),
+                _ => error
+            })?;
+
+        let stdin = child.stdin.take();
+        let stderr = child.stderr.take().map(|mut stderr| thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output);
+            output
+        }));
+        Ok(Self { child, stdin, stderr, size: (width, height) })
+    }
+
+    // Closes stdin and waits for ffmpeg to exit, returning its exit status and whatever it wrote to stderr
+    fn wait(&mut self) -> std::io::Result<(ExitStatus, String)> {
+        drop(self.stdin.take());
+
+        let status = self.child.wait()?;
+        let stderr = self.stderr.take().and_then(|stderr| stderr.join().ok()).unwrap_or_default();
+        Ok((status, stderr.trim().to_string()))
+    }
+}
+
+impl FrameEncoder for FfmpegEncoder {
+    fn encode(&mut self, frame: &RgbaImage) -> std::io::Result<()> {
+        if frame.dimensions() != self.size {
+            return Err(Error::new(ErrorKind::InvalidInput, "frame size does not match the video size"));
+        }
+
+        let stdin = self.stdin.as_mut().ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "the encoder has already finished"))?;
+        let Err(error) = stdin.write_all(frame.as_raw()) else {
+            return Ok(());
+        };
+
+        // a broken pipe only means that ffmpeg has stopped reading, the reason is in how it exited
+        let (status, stderr) = self.wait()?;
+        Err(Error::new(error.kind(), format!("{error}, ffmpeg exited with {status}: {stderr}")))
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        // closing stdin tells ffmpeg that there are no more frames
+        let (status, stderr) = self.wait()?;
+        if !status.success() {
+            return Err(Error::other(format!("ffmpeg exited with {status}: {stderr}")));
+        }
+
+        Ok(())
+    }
+}
+
+// Plays the source through the visualiser as fast as possible, encoding a frame every 1 / frame_rate
+// seconds of audio. Returns the number of frames encoded.
+pub fn render_video<S, E>(source: S, visualiser: &mut Visualiser, encoder: &mut E, size: (u32, u32), frame_rate: u32) -> std::io::Result<usize>
+where
+    S: Source<Item=f32> + Send + 'static,
+    E: FrameEncoder
+{
+    let samples_per_frame = source.sample_rate() as f64 * source.channels() as f64 / frame_rate as f64;
+    let (introspect, mut introspected) = introspect(source, Duration::from_secs(1));
+
+    let mut frames = 0;
+    let mut consumed = 0;
+    loop {
+        let target = ((frames + 1) as f64 * samples_per_frame).round() as usize;
+        let pulled = introspected.by_ref().take(target - consumed).count();
+        // a trailing partial interval doesn't make a frame, a source may end a sample or so past a whole duration
+        if pulled < target - consumed {
+            break;
+        }
+        consumed += pulled;
+
+        visualiser.update(&introspect, 1.0 / frame_rate as f32);
+
+        let mut image = visualiser.render(size, None, 1.0);
+        image.pixels_mut().for_each(|pixel| pixel[3] = u8::MAX);
+        encoder.encode(&image)?;
+
+        frames += 1;
+    }
+
+    encoder.finish()?;
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use image::RgbaImage;
+    use rodio::source::SineWave;
+    use rodio::Source;
+    use crate::video::{FrameEncoder, render_video};
+    use crate::visualiser::Visualiser;
+
+    struct StubEncoder {
+        size: (u32, u32),
+        frames: usize,
+        finished: bool,
+    }
+
+    impl FrameEncoder for StubEncoder {
+        fn encode(&mut self, frame: &RgbaImage) -> std::io::Result<()> {
+            assert!(!self.finished, "frame encoded after finishing");
+            assert_eq!(frame.dimensions(), self.size);
+            self.frames += 1;
+            Ok(())
+        }
+
+        fn finish(&mut self) -> std::io::Result<()> {
+            self.finished = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn encodes_one_frame_per_frame_interval() {
+        let source = SineWave::new(440.0).take_duration(Duration::from_secs(2));
+        let mut encoder = StubEncoder { size: (320, 180), frames: 0, finished: false };
+
+        let frames = render_video(source, &mut Visualiser::default(), &mut encoder, (320, 180), 30).unwrap();
+
+        assert_eq!(frames, 60);
+        assert_eq!(encoder.frames, 60);
+        assert!(encoder.finished);
+    }
+}
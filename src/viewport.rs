@@ -0,0 +1,180 @@
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use crate::fft::fft::Hertz;
+use crate::fft::scale::FrequencyScale;
+
+// Levels the dB axis can be zoomed or panned to
+const LEVEL_LIMITS: RangeInclusive<f32> = -200.0..=40.0;
+
+const DEFAULT_LEVELS: RangeInclusive<f32> = -120.0..=0.0;
+
+// Narrowest spans the axes can be zoomed into, in dB and as a fraction of the whole frequency axis
+const MIN_LEVEL_SPAN: f32 = 6.0;
+const MIN_FREQUENCY_SPAN: f32 = 0.01;
+
+// Time in seconds for the auto-ranged dB axis to close ~63 % of the gap when it shrinks, it grows instantly
+const AUTO_RANGE_RELEASE: f32 = 1.5;
+
+// Room left above the loudest level and below the quietest one when auto-ranging
+const AUTO_RANGE_MARGIN: f32 = 6.0;
+
+// Share of the levels allowed to fall below the bottom of the auto-ranged axis
+const AUTO_RANGE_PERCENTILE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    Full,
+    Bass,
+    Vocals,
+}
+
+impl Preset {
+    fn range(&self) -> Option<RangeInclusive<f32>> {
+        match self {
+            Self::Full => None,
+            Self::Bass => Some(20.0..=250.0),
+            Self::Vocals => Some(80.0..=4000.0),
+        }
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full => write!(f, "Full range"),
+            Self::Bass => write!(f, "Bass"),
+            Self::Vocals => write!(f, "Vocals"),
+        }
+    }
+}
+
+// The visible part of the frequency and dB axes
+pub struct Viewport {
+    // None follows the full range of the scale, which depends on the sample rate
+    pub frequency: Option<RangeInclusive<f32>>,
+    pub level: RangeInclusive<f32>,
+    pub auto_level: bool,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self { frequency: None, level: DEFAULT_LEVELS, auto_level: false }
+    }
+}
+
+impl Viewport {
+    pub fn frequency_range(&self, scale: FrequencyScale, nyquist: Hertz) -> RangeInclusive<f32> {
+        let full = scale.minimum().0..=nyquist.0;
+        self.frequency.clone().map_or(full.clone(), |range| {
+            range.start().max(*full.start())..=range.end().min(*full.end())
+        })
+    }
+
+    pub fn preset(&mut self, preset: Preset) {
+        self.frequency = preset.range();
+        if preset == Preset::Full {
+            self.level = DEFAULT_LEVELS;
+            self.auto_level = false;
+        }
+    }
+
+    // Scales the frequency span by `factor` around `anchor`, a position from 0 (left) to 1 (right) on the axis.
+    // Zooming happens in the units of the scale, so the point under the cursor stays put.
+    pub fn zoom_frequency(&mut self, scale: FrequencyScale, nyquist: Hertz, anchor: f32, factor: f32) {
+        let (full_start, full_end) = (scale.forward(scale.minimum()), scale.forward(nyquist));
+        let range = self.frequency_range(scale, nyquist);
+        let (start, end) = (scale.forward(Hertz(*range.start())), scale.forward(Hertz(*range.end())));
+
+        let centre = start + anchor * (end - start);
+        let span = ((end - start) * factor).clamp((full_end - full_start) * MIN_FREQUENCY_SPAN, full_end - full_start);
+        self.set_frequency(scale, nyquist, centre - anchor * span, span);
+    }
+
+    // Moves the frequency axis by `delta` times its visible width
+    pub fn pan_frequency(&mut self, scale: FrequencyScale, nyquist: Hertz, delta: f32) {
+        let range = self.frequency_range(scale, nyquist);
+        let (start, end) = (scale.forward(Hertz(*range.start())), scale.forward(Hertz(*range.end())));
+        self.set_frequency(scale, nyquist, start + delta * (end - start), end - start);
+    }
+
+    fn set_frequency(&mut self, scale: FrequencyScale, nyquist: Hertz, start: f32, span: f32) {
+        let (full_start, full_end) = (scale.forward(scale.minimum()), scale.forward(nyquist));
+        // not clamp, rounding can leave a full width span a hair wider than the range and clamp panics when min > max
+        let start = start.min(full_end - span).max(full_start);
+        self.frequency = Some(scale.inverse(start).0..=scale.inverse(start + span).0);
+    }
+
+    // Same as the frequency zoom, with the anchor at 0 for the bottom of the axis and 1 for the top
+    pub fn zoom_level(&mut self, anchor: f32, factor: f32) {
+        let (start, end) = (*self.level.start(), *self.level.end());
+        let centre = start + anchor * (end - start);
+        let span = ((end - start) * factor).clamp(MIN_LEVEL_SPAN, LEVEL_LIMITS.end() - LEVEL_LIMITS.start());
+        self.set_level(centre - anchor * span, span);
+        self.auto_level = false;
+    }
+
+    pub fn pan_level(&mut self, delta: f32) {
+        let (start, end) = (*self.level.start(), *self.level.end());
+        self.set_level(start + delta * (end - start), end - start);
+        self.auto_level = false;
+    }
+
+    fn set_level(&mut self, start: f32, span: f32) {
+        let start = start.min(LEVEL_LIMITS.end() - span).max(*LEVEL_LIMITS.start());
+        self.level = start..=start + span;
+    }
+
+    // Fits the dB axis around the given levels, widening straight away and narrowing slowly so that it doesn't jump around.
+    // The bottom follows a low percentile rather than the minimum, which would sink into the deepest notch.
+    pub fn auto_range<I: IntoIterator<Item=f32>>(&mut self, levels: I, delta_time: f32) {
+        let mut levels = levels.into_iter().filter(|level| level.is_finite()).collect::<Vec<_>>();
+        if levels.is_empty() {
+            return;
+        }
+
+        let loudest = levels.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let index = ((levels.len() - 1) as f32 * AUTO_RANGE_PERCENTILE) as usize;
+        let quietest = *levels.select_nth_unstable_by(index, f32::total_cmp).1;
+
+        // round to 10 dB so that the ticks stay on round numbers
+        let target_end = ((loudest + AUTO_RANGE_MARGIN) / 10.0).ceil() * 10.0;
+        let target_start = ((quietest - AUTO_RANGE_MARGIN) / 10.0).floor() * 10.0;
+        let target_start = target_start.min(target_end - MIN_LEVEL_SPAN);
+
+        let release = 1.0 - (-delta_time / AUTO_RANGE_RELEASE).exp();
+        let approach = |current: f32, target: f32, grows: bool| if grows { target } else { current + (target - current) * release };
+
+        let start = approach(*self.level.start(), target_start, target_start < *self.level.start());
+        let end = approach(*self.level.end(), target_end, target_end > *self.level.end());
+        self.level = start.max(*LEVEL_LIMITS.start())..=end.min(*LEVEL_LIMITS.end());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fft::fft::Hertz;
+    use crate::fft::scale::FrequencyScale;
+    use crate::viewport::Viewport;
+
+    #[test]
+    fn zooming_out_and_panning_at_full_range_stays_in_range() {
+        let mut scale = FrequencyScale::Linear;
+        loop {
+            for nyquist in [8000.0, 11025.0, 16000.0, 22050.0, 24000.0, 48000.0].map(Hertz) {
+                let mut viewport = Viewport::default();
+                viewport.zoom_frequency(scale, nyquist, 0.5, 2.0);
+                viewport.pan_frequency(scale, nyquist, 0.25);
+                viewport.pan_frequency(scale, nyquist, -0.25);
+                viewport.zoom_frequency(scale, nyquist, 0.0, 2.0);
+
+                let range = viewport.frequency_range(scale, nyquist);
+                assert!(*range.start() >= scale.minimum().0 && range.start() < range.end() && *range.end() <= nyquist.0, "{scale} at {}: {range:?}", nyquist.0);
+            }
+
+            scale = scale.next();
+            if scale == FrequencyScale::Linear {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,823 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+use image::{Rgba, RgbaImage};
+use imageproc::rect::Rect;
+use itertools::{EitherOrBoth, Itertools};
+use nannou::math::map_range;
+use realfft::RealFftPlanner;
+use crate::audio::introspect::Introspectable;
+use crate::audio::pitch::{Pitch, PitchDetector};
+use crate::fft::averaging::{Averager, Averaging, Ballistics, Domain};
+use crate::fft::bands::{Band, band_levels, OctaveFraction, spectrum_power};
+use crate::fft::chroma::{Chroma, KeyEstimator};
+use crate::fft::features::{FeatureExtractor, Features, mix};
+use crate::fft::peaks::{Peak, strongest_peaks};
+use crate::fft::reference::Reference;
+use crate::fft::scale::FrequencyScale;
+use crate::fft::scaling::{Calibration, Scaling};
+use crate::fft::tempo::BeatTracker;
+use crate::fft::welch::{TryIntoPowerSpectralDensity, Welch};
+use crate::fft::weighting::{Tilt, Weighting};
+use crate::fft::fft::{AudioView, CONCERT_PITCH, FrequencySpectrum, Hertz, Note, TryIntoFrequencySpectrum};
+use crate::numtools::{lerp, lerp_index_fn, to_dbfs};
+use crate::renderer::{Renderer, SoftwareRenderer};
+use crate::ticks::{frequency_ticks, level_ticks, spaced, Tick};
+use crate::viewport::Viewport;
+
+// Analysis state and display settings, independent of any window or audio device
+pub struct Visualiser {
+    pub planner: RealFftPlanner<f32>,
+    pub spectra: Vec<FrequencySpectrum>,
+
+    pub axis: FrequencyAxis,
+    pub scale: FrequencyScale,
+    pub viewport: Viewport,
+    pub tuning: Hertz,
+    pub show_keyboard: bool,
+
+    pub pitch_detector: PitchDetector,
+    pub pitch: Option<Pitch>,
+    pub show_tuner: bool,
+
+    pub view_mode: ViewMode,
+    pub chroma_history: VecDeque<Chroma>,
+    pub key_estimator: KeyEstimator,
+
+    pub beat_tracker: BeatTracker,
+    pub pulse: f32,
+    pub show_beats: bool,
+
+    pub peaks: Vec<Peak>,
+    pub show_peaks: bool,
+    pub show_crosshair: bool,
+
+    pub bands: Vec<Band>,
+    pub octave_fraction: OctaveFraction,
+    // mean square of the weighted signal, averaged over the channels
+    pub power: f32,
+    // the same without the weighting, which calibration is done against
+    pub unweighted_power: f32,
+
+    pub scaling: Scaling,
+    pub calibration: Option<Calibration>,
+    // averages segments of the whole buffer instead of taking single frames, for noise
+    pub welch: Option<Welch>,
+
+    pub weighting: Weighting,
+    pub tilt: Tilt,
+
+    pub traces: Vec<Trace>,
+    pub ballistics: Ballistics,
+
+    pub reference: Option<Reference>,
+    pub show_difference: bool,
+
+    pub feature_extractor: FeatureExtractor,
+    pub features: Option<Features>,
+    pub show_features: bool,
+}
+
+// An averaged spectrum overlaid on top of the live one
+pub struct Trace {
+    pub averager: Averager,
+    pub colour: Rgba<u8>,
+    pub visible: bool,
+}
+
+impl Trace {
+    fn new(mode: Averaging, colour: [u8; 3]) -> Self {
+        Self { averager: Averager::new(mode), colour: Rgba([colour[0], colour[1], colour[2], u8::MAX]), visible: false }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ViewMode {
+    Spectrum,
+    Bands,
+    Chromagram,
+}
+
+// Number of frames kept for the chromagram
+const CHROMA_HISTORY: usize = 512;
+
+// Range of the difference curve, in dB either side of the centre line
+const DIFFERENCE_RANGE: f32 = 24.0;
+
+// Gridline colours, faint enough to sit behind the curves
+const GRID: Rgba<u8> = Rgba([40, 40, 40, u8::MAX]);
+const MAJOR_GRID: Rgba<u8> = Rgba([72, 72, 72, u8::MAX]);
+
+// Frequencies covered by the band analyser, the usual audible range
+const BAND_RANGE: RangeInclusive<f32> = 20.0..=20000.0;
+
+// Frequencies folded into the chromagram, roughly A1 to D#8
+const CHROMA_RANGE: RangeInclusive<f32> = 55.0..=5000.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FrequencyAxis {
+    Hertz,
+    Notes,
+}
+
+impl Default for Visualiser {
+    fn default() -> Self {
+        Self {
+            planner: RealFftPlanner::new(),
+            spectra: Vec::new(),
+
+            axis: FrequencyAxis::Hertz,
+            scale: FrequencyScale::Logarithmic,
+            viewport: Viewport::default(),
+            tuning: CONCERT_PITCH,
+            show_keyboard: false,
+
+            pitch_detector: PitchDetector::default(),
+            pitch: None,
+            show_tuner: false,
+
+            view_mode: ViewMode::Spectrum,
+            chroma_history: VecDeque::with_capacity(CHROMA_HISTORY),
+            key_estimator: KeyEstimator::default(),
+
+            beat_tracker: BeatTracker::default(),
+            pulse: 0.0,
+            show_beats: false,
+
+            peaks: Vec::new(),
+            show_peaks: false,
+            show_crosshair: true,
+
+            bands: Vec::new(),
+            octave_fraction: OctaveFraction::Third,
+            power: 0.0,
+            unweighted_power: 0.0,
+
+            scaling: Scaling::AmplitudePeak,
+            calibration: None,
+            welch: None,
+
+            weighting: Weighting::Z,
+            tilt: Tilt::default(),
+
+            traces: vec![
+                Trace::new(Averaging::Exponential { time_constant: 1.0, domain: Domain::Power }, [255, 200, 64]),
+                Trace::new(Averaging::Exponential { time_constant: 1.0, domain: Domain::Decibel }, [255, 128, 0]),
+                Trace::new(Averaging::Linear { frames: 32 }, [64, 255, 128]),
+                Trace::new(Averaging::Infinite, [64, 200, 255]),
+                Trace::new(Averaging::MaxHold, [255, 64, 64]),
+                Trace::new(Averaging::MinHold, [160, 96, 255]),
+            ],
+            ballistics: Ballistics::default(),
+
+            reference: None,
+            show_difference: false,
+
+            feature_extractor: FeatureExtractor::default(),
+            features: None,
+            show_features: false,
+        }
+    }
+}
+
+impl Visualiser {
+    // Forgets everything learnt about the previous song
+    pub fn reset_analysis(&mut self) {
+        self.key_estimator.reset();
+        self.beat_tracker.reset();
+    }
+
+    pub fn update(&mut self, introspect: &Introspectable<f32>, delta_time: f32) {
+        // FFT size for the low frequency resolution, high time resolution transform
+        const LOW_RES_FFT_SIZE: usize = 8192;
+
+        // FFT size for onset detection, short enough to resolve individual drum hits
+        const ONSET_FFT_SIZE: usize = 2048;
+
+        // Time in seconds for a visual pulse to decay to ~37 %
+        const PULSE_DECAY: f32 = 0.15;
+
+        // The blended spectra are for display only, levels are read from the raw high resolution transform (or the
+        // Welch estimate) of each channel as the blend and the ballistics would skew them
+        let filled = introspect.filled();
+        let (fresh, raw): (Vec<_>, Vec<_>) = introspect.audio_views().iter().map(|view| {
+            if let Some(welch) = self.welch {
+                // averaging in the zero padding of a buffer that is still filling up would read low, an empty one is silent
+                // either way
+                let psd = match filled {
+                    0..=1 => view.try_into_psd(&mut self.planner, welch),
+                    _ => view.subview(0..filled).try_into_psd(&mut self.planner, welch)
+                };
+                let spectrum = psd.unwrap().to_spectrum();
+                return (spectrum.clone(), spectrum);
+            }
+
+            let lo_res_spectrum = view.subview(0..LOW_RES_FFT_SIZE).try_into_spectrum(&mut self.planner).unwrap();
+            let hi_res_spectrum = view.try_into_spectrum(&mut self.planner).unwrap();
+
+            let blended = hi_res_spectrum.merge(&lo_res_spectrum, |_, (frequency, value), other| {
+                let approximate_bin = other.hertz_to_bin(frequency);
+                lerp(value, lerp_index_fn(|x| other.get(x), approximate_bin, 0f32), 0.6f32)
+            });
+            (blended, hi_res_spectrum)
+        }).unzip();
+
+        for trace in self.traces.iter_mut().filter(|trace| trace.visible) {
+            trace.averager.push(&fresh, delta_time);
+        }
+
+        // measured before the ballistics, which would smear the flux
+        self.features = if self.show_features {
+            Some(self.feature_extractor.process(mix(&fresh)))
+        } else {
+            self.feature_extractor.reset();
+            None
+        };
+
+        self.spectra = self.spectra.iter().cloned().zip_longest(fresh).map(|eob| {
+            match eob {
+                EitherOrBoth::Both(old, new) => self.ballistics.apply(&old, &new, delta_time),
+                EitherOrBoth::Left(_) => FrequencySpectrum::default(),
+                EitherOrBoth::Right(new) => new
+            }
+        }).collect();
+
+        let chroma = self.spectra.iter()
+            .map(|spectrum| Chroma::from_spectrum(spectrum, self.tuning, CHROMA_RANGE))
+            .fold(Chroma::default(), |sum, chroma| sum.sum(&chroma));
+
+        self.key_estimator.update(&chroma, delta_time);
+        self.chroma_history.push_back(chroma.normalised());
+        while self.chroma_history.len() > CHROMA_HISTORY {
+            self.chroma_history.pop_front();
+        }
+
+        let mono = introspect.downmix();
+        if mono.len() >= ONSET_FFT_SIZE {
+            let onset_view = AudioView::new(introspect.sample_rate(), Mutex::new(mono[..ONSET_FFT_SIZE].into()));
+            let previous_phase = self.beat_tracker.tempo().map(|tempo| tempo.phase);
+
+            if let Ok(spectrum) = onset_view.try_into_spectrum(&mut self.planner) {
+                let onset = self.beat_tracker.process(spectrum, delta_time);
+                let beat = match (previous_phase, self.beat_tracker.tempo()) {
+                    (Some(previous), Some(tempo)) => previous > 0.75 && tempo.phase < 0.25,
+                    _ => false
+                };
+
+                self.pulse *= (-delta_time / PULSE_DECAY).exp();
+                if beat {
+                    self.pulse = 1.0;
+                } else if onset {
+                    self.pulse = self.pulse.max(0.5);
+                }
+            }
+        }
+
+        let weighted = self.spectra.iter().map(|spectrum| self.weighting.apply(spectrum)).collect::<Vec<_>>();
+
+        if self.viewport.auto_level && let Some(nyquist) = weighted.first().map(|spectrum| Hertz(spectrum.nyquist_frequency() as f32)) {
+            let range = self.viewport.frequency_range(self.scale, nyquist);
+            let levels = weighted.iter().flat_map(|spectrum| spectrum.iter().enumerate()
+                .filter(|(_, (frequency, _))| range.contains(&frequency.0))
+                .filter_map(|(bin, (frequency, magnitude))| {
+                    self.scaling.level(spectrum, bin as f32, magnitude).map(|level| level + self.tilt.gain_db(frequency))
+                }));
+            self.viewport.auto_range(levels, delta_time);
+        }
+
+        let weighted_raw = raw.iter().map(|spectrum| self.weighting.apply(spectrum)).collect::<Vec<_>>();
+
+        self.power = weighted_raw.iter().map(spectrum_power).sum::<f32>() / weighted_raw.len().max(1) as f32;
+        self.unweighted_power = raw.iter().map(spectrum_power).sum::<f32>() / raw.len().max(1) as f32;
+
+        // Channels are combined by averaging their band powers, and the bars then get the same ballistics as the spectrum
+        self.bands = if self.view_mode == ViewMode::Bands {
+            let channels = weighted_raw.iter()
+                .map(|spectrum| band_levels(spectrum, self.octave_fraction, BAND_RANGE))
+                .collect::<Vec<_>>();
+
+            let bands = channels.first().map_or(Vec::new(), |first| first.iter().enumerate().map(|(index, band)| Band {
+                power: channels.iter().map(|bands| bands[index].power).sum::<f32>() / channels.len() as f32,
+                ..*band
+            }).collect::<Vec<_>>());
+
+            if self.bands.len() == bands.len() {
+                bands.iter().zip(&self.bands).map(|(band, old)| Band {
+                    power: self.ballistics.smooth(old.power, band.power, band.centre, delta_time),
+                    ..*band
+                }).collect()
+            } else {
+                bands
+            }
+        } else {
+            Vec::new()
+        };
+
+        // Number of peaks labelled, and the quietest level considered a peak
+        const PEAK_COUNT: usize = 5;
+        const PEAK_THRESHOLD: f32 = -90.0;
+
+        self.peaks = if self.show_peaks {
+            strongest_peaks(&weighted_raw, PEAK_THRESHOLD, PEAK_COUNT, Hertz(20.0), Hertz(20.0))
+        } else {
+            Vec::new()
+        };
+
+        self.pitch = if self.show_tuner {
+            self.pitch_detector.detect_introspectable(introspect)
+        } else {
+            None
+        };
+    }
+
+    // Draws the visualisation into a new image with the software renderer, with a transparent background
+    pub fn render(&self, size: (u32, u32), cursor: Option<(f32, f32)>, scale: f32) -> RgbaImage {
+        let mut renderer = SoftwareRenderer::new(size);
+        self.draw(&mut renderer, cursor, scale);
+        renderer.into_image()
+    }
+
+    // `scale` multiplies text and every fixed distance in pixels, for images larger than the window
+    pub fn draw(&self, renderer: &mut dyn Renderer, cursor: Option<(f32, f32)>, scale: f32) {
+        let (width, height) = renderer.size();
+        let px = |pixels: f32| pixels * scale;
+
+        let (margin_x, margin_y, drawing_area_width, drawing_area_height) = plot_area((width, height));
+        let (drawing_area_width, drawing_area_height) = (drawing_area_width as usize, drawing_area_height as usize);
+
+        // the viewport is in uncalibrated levels, the offset only shifts the labels
+        let offset = self.calibration.map_or(0.0, |calibration| calibration.offset);
+        let unit = self.unit();
+
+        let (min_dbfs, max_dbfs) = (*self.viewport.level.start(), *self.viewport.level.end());
+        let dbfs_to_y = |amplitude| {
+            margin_y + map_range(map_range(amplitude, min_dbfs, max_dbfs, 0f32, 1f32)
+                          .clamp(0.0, 1.0), 0.0, 1.0, drawing_area_height as f32, 0.0)
+        };
+
+        if self.show_beats {
+            let tint = (self.pulse * 48.0) as u8;
+            renderer.fill(Rgba([tint, tint / 2, tint, u8::MAX]));
+        }
+
+        match self.view_mode {
+            ViewMode::Spectrum | ViewMode::Bands => {
+                let (left, right) = (margin_x, margin_x + drawing_area_width as f32);
+                let (top, bottom) = (margin_y, margin_y + drawing_area_height as f32);
+
+                // labels need at least their own height between them, and frequency labels about this much width
+                let (_, label_height) = renderer.text_size(px(16.0), "0");
+                let (label_width, _) = renderer.text_size(px(16.0), "00.0k");
+
+                let level_ticks = level_ticks(&(min_dbfs + offset..=max_dbfs + offset), drawing_area_height as f32, label_height as f32 * 2.0);
+                for tick in &level_ticks {
+                    let y = dbfs_to_y(tick.value - offset);
+                    renderer.line((left as i32, y as i32), (right as i32, y as i32), GRID);
+                }
+
+                if let Some(nyquist) = self.spectra.first().map(|spectrum| spectrum.nyquist_frequency() as f32) {
+                    let range = self.viewport.frequency_range(self.scale, Hertz(nyquist));
+
+                    // the same mapping places curves, ticks and the cursor readout
+                    let x_to_hertz = |x: f32| {
+                        self.scale.denormalise((x - margin_x) / drawing_area_width as f32, &range)
+                    };
+                    let hertz_to_x = |hertz: f32| {
+                        margin_x + self.scale.normalise(Hertz(hertz), &range) * drawing_area_width as f32
+                    };
+
+                    let frequency_ticks = match self.axis {
+                        FrequencyAxis::Hertz => frequency_ticks(self.scale, &range, drawing_area_width as f32, label_width as f32 + px(12.0)),
+                        FrequencyAxis::Notes => {
+                            // every C from C-1 up, then the other naturals wherever there is room
+                            let naturals = |pitch_classes: &[usize]| (0..128).map(Note::new)
+                                .filter(|note| pitch_classes.contains(&note.pitch_class()))
+                                .map(|note| Tick { major: note.pitch_class() == 0, ..Tick::new(note.to_hertz(self.tuning).0, note.to_string()) })
+                                .filter(|tick| range.contains(&tick.value))
+                                .collect::<Vec<_>>();
+                            spaced([naturals(&[0]), naturals(&[7]), naturals(&[4, 9]), naturals(&[2, 5, 11])],
+                                   &hertz_to_x, label_width as f32)
+                        }
+                    };
+
+                    for tick in &frequency_ticks {
+                        let x = hertz_to_x(tick.value);
+                        let colour = if tick.major { MAJOR_GRID } else { GRID };
+                        renderer.line((x as i32, top as i32), (x as i32, bottom as i32), colour);
+                    }
+
+                    if self.show_keyboard {
+                        draw_keyboard(renderer, self.tuning, &hertz_to_x,
+                                      (margin_x, margin_x + drawing_area_width as f32),
+                                      margin_y + drawing_area_height as f32);
+                    }
+
+                    if self.view_mode == ViewMode::Bands {
+                        draw_bands(renderer, &self.bands, self.scaling, self.tilt, &hertz_to_x, &dbfs_to_y,
+                                   (margin_x, margin_x + drawing_area_width as f32));
+                        renderer.text(((margin_x + drawing_area_width as f32 - px(200.0)) as i32, (margin_y * 0.2) as i32),
+                                      px(16.0), Rgba([u8::MAX; 4]), &*format!("{}", self.octave_fraction));
+
+                        let level = self.scaling.power_level(self.power, Hertz(nyquist))
+                            .map_or("-inf".to_string(), |level| format!("{:.1}", level + offset));
+                        renderer.text(((margin_x + drawing_area_width as f32 - px(200.0)) as i32, (margin_y * 0.2 + px(18.0)) as i32),
+                                      px(16.0), Rgba([u8::MAX; 4]), &*format!("Level: {level} {unit}"));
+                    }
+
+                    let draw_spectrum = |renderer: &mut dyn Renderer, spectrum: &FrequencySpectrum, colour: Rgba<u8>| {
+                        let mut px = None;
+                        let mut py = None;
+
+                        for x in (margin_x as usize)..(margin_x as usize + drawing_area_width) {
+                            // a column covering several bins shows the loudest of them, so that narrow peaks keep their level
+                            // when zoomed out; one falling between two bins interpolates them
+                            let (lower, upper) = (spectrum.hertz_to_bin(x_to_hertz(x as f32 - 0.5)), spectrum.hertz_to_bin(x_to_hertz(x as f32 + 0.5)));
+                            let loudest = (lower.ceil() as usize..=upper.floor() as usize)
+                                .filter_map(|bin| Some((bin, spectrum.get(bin)? * self.weighting.gain(spectrum.bin_to_hertz(bin)))))
+                                .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+                            let hertz = x_to_hertz(x as f32);
+                            let (index, sample) = match loudest {
+                                Some((bin, sample)) => (bin as f32, sample),
+                                None => {
+                                    let index = spectrum.hertz_to_bin(hertz);
+                                    (index, lerp_index_fn(|index| { spectrum.get(index) }, index, 0.0) * self.weighting.gain(hertz))
+                                }
+                            };
+
+                            // skip if NaN, inf, or some other nasty number
+                            let Some(amplitude) = self.scaling.level(spectrum, index, sample) else {
+                                continue;
+                            };
+
+                            let y = dbfs_to_y(amplitude + self.tilt.gain_db(hertz));
+
+                            renderer.line((px.unwrap_or(x) as i32, py.unwrap_or(y) as i32), (x as i32, y as i32), colour);
+
+                            px = Some(x);
+                            py = Some(y);
+                        }
+                    };
+
+                    if self.view_mode == ViewMode::Spectrum {
+                        for trace in self.traces.iter().filter(|trace| trace.visible) {
+                            for spectrum in trace.averager.spectra() {
+                                draw_spectrum(&mut *renderer, spectrum, trace.colour);
+                            }
+                        }
+
+                        if let Some(reference) = &self.reference {
+                            for spectrum in &reference.spectra {
+                                draw_spectrum(&mut *renderer, spectrum, Rgba([0, 220, 220, u8::MAX]));
+                            }
+
+                            if self.show_difference {
+                                let centre = margin_y + drawing_area_height as f32 / 2.0;
+                                renderer.line((margin_x as i32, centre as i32), ((margin_x + drawing_area_width as f32) as i32, centre as i32),
+                                              Rgba([96, 96, 96, u8::MAX]));
+
+                                for ratio in reference.difference(&self.spectra) {
+                                    let mut previous = None;
+                                    for x in (margin_x as usize)..(margin_x as usize + drawing_area_width) {
+                                        let index = ratio.hertz_to_bin(x_to_hertz(x as f32));
+                                        let Some(difference) = to_dbfs(lerp_index_fn(|index| ratio.get(index), index, 0.0)) else {
+                                            continue;
+                                        };
+
+                                        let y = centre - (difference / DIFFERENCE_RANGE).clamp(-1.0, 1.0) * drawing_area_height as f32 / 2.0;
+                                        let (px, py) = previous.unwrap_or((x, y));
+                                        renderer.line((px as i32, py as i32), (x as i32, y as i32), Rgba([255, 64, 255, u8::MAX]));
+                                        previous = Some((x, y));
+                                    }
+                                }
+
+                                renderer.text(((margin_x + drawing_area_width as f32 - px(100.0)) as i32, (margin_y + px(4.0)) as i32),
+                                              px(14.0), Rgba([255, 64, 255, u8::MAX]), &*format!("Diff: ±{DIFFERENCE_RANGE:.0} dB"));
+                            }
+                        }
+
+                        for spectrum in &self.spectra {
+                            draw_spectrum(&mut *renderer, spectrum, Rgba([u8::MAX; 4]));
+                        }
+
+                        if let Some(welch) = self.welch {
+                            renderer.text(((margin_x + drawing_area_width as f32 - px(200.0)) as i32, (margin_y * 0.2) as i32),
+                                          px(16.0), Rgba([u8::MAX; 4]), &*format!("{welch}"));
+                        }
+
+                        for (row, trace) in self.traces.iter().filter(|trace| trace.visible).enumerate() {
+                            renderer.text(((margin_x + px(8.0)) as i32, (margin_y + px(4.0 + row as f32 * 16.0)) as i32),
+                                          px(14.0), trace.colour, &*format!("{}", trace.averager.mode));
+                        }
+                    }
+
+                    // centred under their tick, but kept inside the image
+                    let (image_width, image_height) = (width as i32, height as i32);
+                    for tick in &frequency_ticks {
+                        let (width, _) = renderer.text_size(px(16.0), &tick.label);
+                        let x = (hertz_to_x(tick.value) as i32 - width / 2).clamp(0, (image_width - width).max(0));
+                        renderer.text((x, image_height - (margin_y * 0.9) as i32), px(16.0), Rgba([u8::MAX; 4]), &tick.label);
+                    }
+
+                    if self.show_peaks && let Some(spectrum) = self.spectra.first() {
+                        for peak in &self.peaks {
+                            let Some(level) = self.scaling.level(spectrum, peak.bin, peak.amplitude) else { continue };
+                            let (x, y) = (hertz_to_x(peak.frequency.0), dbfs_to_y(level + self.tilt.gain_db(peak.frequency)));
+
+                            let size = px(5.0) as u32;
+                            renderer.rect(Rect::at((x - px(2.0)) as i32, (y - px(2.0)) as i32).of_size(size, size), Rgba([255, 64, 64, u8::MAX]));
+                            renderer.text(((x + px(4.0)) as i32, (y - px(20.0)) as i32), px(14.0), Rgba([255, 160, 160, u8::MAX]),
+                                          &*format!("{:.1} Hz, {:.1} {unit}", peak.frequency.0, level + offset));
+                        }
+                    }
+
+                    let inside = |(x, y): &(f32, f32)| (margin_x..margin_x + drawing_area_width as f32).contains(x)
+                        && (margin_y..margin_y + drawing_area_height as f32).contains(y);
+
+                    if self.show_crosshair && let Some((mouse_x, mouse_y)) = cursor.filter(inside) {
+                        let faint = Rgba([128, 128, 128, u8::MAX]);
+                        renderer.line((mouse_x as i32, margin_y as i32), (mouse_x as i32, (margin_y + drawing_area_height as f32) as i32),
+                                      faint);
+                        renderer.line((margin_x as i32, mouse_y as i32), ((margin_x + drawing_area_width as f32) as i32, mouse_y as i32),
+                                      faint);
+
+                        let hertz = x_to_hertz(mouse_x);
+                        let levels = self.spectra.iter().map(|spectrum| {
+                            let index = spectrum.hertz_to_bin(hertz);
+                            let sample = lerp_index_fn(|index| spectrum.get(index), index, 0.0);
+                            self.scaling.level(spectrum, index, sample * self.weighting.gain(hertz))
+                                .map_or(format!("-inf {unit}"), |level| format!("{:.1} {unit}", level + offset))
+                        }).join(" / ");
+
+                        renderer.text(((mouse_x + px(8.0)) as i32, (mouse_y + px(8.0)) as i32), px(14.0), Rgba([u8::MAX; 4]),
+                                      &*format!("{:.1} Hz ({}): {levels}", hertz.0, hertz.to_note(self.tuning)));
+                    }
+                }
+
+                renderer.text((margin_x as i32 / 10, (margin_y * 0.2) as i32), px(16.0), Rgba([u8::MAX; 4]), &*unit);
+
+                if self.tilt.slope != 0.0 {
+                    renderer.text(((margin_x + drawing_area_width as f32 - px(200.0)) as i32, (margin_y * 0.2 + px(36.0)) as i32),
+                                  px(16.0), Rgba([u8::MAX; 4]), &*format!("Tilt: {}", self.tilt));
+                }
+
+                for tick in &level_ticks {
+                    let y = dbfs_to_y(tick.value - offset) as i32 - label_height / 2;
+                    renderer.text((margin_x as i32 / 10, y), px(16.0), Rgba([u8::MAX; 4]), &tick.label);
+                }
+            }
+            ViewMode::Chromagram => {
+                draw_chromagram(renderer, &self.chroma_history, (margin_x, margin_y),
+                                (drawing_area_width as f32, drawing_area_height as f32), scale);
+
+                let estimate = self.key_estimator.estimate()
+                    .map_or("?".to_string(), |(key, correlation)| format!("{key} ({correlation:.2})"));
+                renderer.text(((margin_x + drawing_area_width as f32 - px(200.0)) as i32, (margin_y * 0.2) as i32),
+                              px(16.0), Rgba([u8::MAX; 4]), &*format!("Key: {estimate}"));
+            }
+        }
+
+        if self.axis == FrequencyAxis::Notes {
+            renderer.text(((margin_x + px(50.0)) as i32, (margin_y * 0.2) as i32), px(16.0), Rgba([u8::MAX; 4]),
+                          &*format!("A4 = {:.0} Hz", self.tuning.0));
+        }
+
+        if self.show_beats {
+            let tempo = self.beat_tracker.tempo()
+                .map_or("? BPM".to_string(), |tempo| format!("{:.1} BPM ({:.0} %)", tempo.bpm, tempo.confidence * 100.0));
+            renderer.text(((margin_x + px(180.0)) as i32, (margin_y * 0.2) as i32), px(16.0), Rgba([u8::MAX; 4]), &*tempo);
+
+            let brightness = (64.0 + self.pulse * 191.0) as u8;
+            let size = px(12.0) as u32;
+            renderer.rect(Rect::at((margin_x + px(160.0)) as i32, (margin_y * 0.2 + px(2.0)) as i32).of_size(size, size),
+                          Rgba([brightness, brightness / 2, brightness, u8::MAX]));
+        }
+
+        if self.show_tuner {
+            draw_tuner(renderer, self.pitch, self.tuning, margin_y, scale);
+        }
+
+        if let Some(features) = &self.features {
+            for (row, line) in features.to_string().lines().enumerate() {
+                renderer.text(((margin_x + drawing_area_width as f32 - px(160.0)) as i32, (margin_y + px(24.0 + row as f32 * 16.0)) as i32),
+                              px(14.0), Rgba([160, 255, 160, u8::MAX]), line);
+            }
+        }
+    }
+}
+
+impl Visualiser {
+    // Unit of every level shown, following the scaling, weighting and calibration
+    pub fn unit(&self) -> String {
+        self.scaling.unit(self.weighting, self.calibration.is_some())
+    }
+
+    // Takes the current level as that of a calibrator producing `level` dB SPL. Read unweighted, as calibrators are
+    // specified, so that the offset holds for every weighting.
+    pub fn calibrate(&mut self, level: f32) -> Option<Calibration> {
+        self.calibration = Calibration::from_power(self.unweighted_power, level);
+        self.calibration
+    }
+
+    fn nyquist(&self) -> Option<Hertz> {
+        self.spectra.first().map(|spectrum| Hertz(spectrum.nyquist_frequency() as f32))
+    }
+
+    // Zooms the frequency axis, or the dB axis, by `factor` around the cursor in an image of this size
+    pub fn zoom(&mut self, size: (u32, u32), (x, y): (f32, f32), factor: f32, levels: bool) {
+        let (left, top, width, height) = plot_area(size);
+        if levels {
+            self.viewport.zoom_level((1.0 - (y - top) / height).clamp(0.0, 1.0), factor);
+        } else if let Some(nyquist) = self.nyquist() {
+            self.viewport.zoom_frequency(self.scale, nyquist, ((x - left) / width).clamp(0.0, 1.0), factor);
+        }
+    }
+
+    // Drags both axes along with the cursor, by a distance in pixels
+    pub fn pan(&mut self, size: (u32, u32), (dx, dy): (f32, f32)) {
+        let (_, _, width, height) = plot_area(size);
+        if let Some(nyquist) = self.nyquist() && dx != 0.0 {
+            self.viewport.pan_frequency(self.scale, nyquist, -dx / width);
+        }
+        if dy != 0.0 {
+            self.viewport.pan_level(dy / height);
+        }
+    }
+}
+
+// Left and top edges, width and height of the plot inside an image of this size
+pub fn plot_area((width, height): (u32, u32)) -> (f32, f32, f32, f32) {
+    const MARGIN: f32 = 0.05;
+
+    let margin_y = MARGIN * height as f32;
+    let margin_x = margin_y;
+    (margin_x, margin_y, (width as f32 - margin_x * 2.0).floor(), (height as f32 - margin_y * 2.0).floor())
+}
+
+fn draw_keyboard<F>(renderer: &mut dyn Renderer, tuning: Hertz, hertz_to_x: F, (left, right): (f32, f32), bottom: f32)
+where
+    F: Fn(f32) -> f32
+{
+    let height = renderer.size().1 as f32 * 0.04;
+    let top = bottom - height;
+
+    let key_span = |note: Note, (lower, upper): (f32, f32)| {
+        let from = hertz_to_x(Note { cents: lower, ..note }.to_hertz(tuning).0).max(left);
+        let to = hertz_to_x(Note { cents: upper, ..note }.to_hertz(tuning).0).min(right);
+        (from < to).then_some((from, to))
+    };
+
+    let (naturals, accidentals): (Vec<_>, Vec<_>) = (0..128).map(Note::new).partition(Note::is_natural);
+
+    // natural keys extend halfway to their natural neighbours so that they tile without gaps
+    for note in naturals {
+        let lower = if Note::new(note.midi - 1).is_natural() { -50.0 } else { -100.0 };
+        let upper = if Note::new(note.midi + 1).is_natural() { 50.0 } else { 100.0 };
+        let Some((from, to)) = key_span(note, (lower, upper)) else { continue };
+
+        let rect = Rect::at(from as i32, top as i32).of_size(((to - from) as u32).saturating_sub(1).max(1), height as u32);
+        renderer.rect(rect, Rgba([200, 200, 200, u8::MAX]));
+    }
+
+    for note in accidentals {
+        let Some((from, to)) = key_span(note, (-50.0, 50.0)) else { continue };
+
+        let rect = Rect::at(from as i32, top as i32).of_size(((to - from) as u32).max(1), (height * 0.6) as u32);
+        renderer.rect(rect, Rgba([30, 30, 30, u8::MAX]));
+    }
+}
+
+fn draw_tuner(renderer: &mut dyn Renderer, pitch: Option<Pitch>, tuning: Hertz, top: f32, scale: f32) {
+    const METER_WIDTH: f32 = 300.0;
+
+    let px = |pixels: f32| pixels * scale;
+    let centre = renderer.size().0 as f32 / 2.0;
+    let meter_y = top + px(40.0);
+    let meter_width = px(METER_WIDTH);
+
+    let Some(pitch) = pitch else {
+        renderer.text(((centre - px(10.0)) as i32, top as i32), px(32.0), Rgba([128, 128, 128, u8::MAX]), "-");
+        return;
+    };
+
+    let note = pitch.frequency.to_note(tuning);
+    let colour = if note.cents.abs() < 5.0 { Rgba([64, 255, 64, u8::MAX]) } else { Rgba([255, 160, 32, u8::MAX]) };
+
+    renderer.text(((centre - px(60.0)) as i32, top as i32), px(32.0), colour, &*format!("{note} {:+.0}", note.cents));
+    renderer.text(((centre + px(60.0)) as i32, (top + px(12.0)) as i32), px(16.0), Rgba([u8::MAX; 4]),
+                  &*format!("{:.1} Hz ({:.0} %)", pitch.frequency.0, pitch.confidence * 100.0));
+
+    // cents meter, spanning -50 to +50 cents
+    renderer.line(((centre - meter_width / 2.0) as i32, meter_y as i32), ((centre + meter_width / 2.0) as i32, meter_y as i32),
+                  Rgba([u8::MAX; 4]));
+    renderer.line((centre as i32, (meter_y - px(6.0)) as i32), (centre as i32, (meter_y + px(6.0)) as i32), Rgba([u8::MAX; 4]));
+
+    let marker_x = centre + note.cents / 100.0 * meter_width;
+    renderer.rect(Rect::at((marker_x - px(2.0)) as i32, (meter_y - px(8.0)) as i32).of_size(px(5.0) as u32, px(17.0) as u32), colour);
+}
+
+fn draw_chromagram(renderer: &mut dyn Renderer, history: &VecDeque<Chroma>, (left, top): (f32, f32), (width, height): (f32, f32), scale: f32) {
+    let column_width = width / CHROMA_HISTORY as f32;
+    let row_height = height / 12.0;
+
+    // newest frame on the right, C on the bottom row
+    let offset = CHROMA_HISTORY - history.len();
+    for (column, chroma) in history.iter().enumerate() {
+        let x = left + (column + offset) as f32 * column_width;
+        for pitch_class in 0..12 {
+            let y = top + (11 - pitch_class) as f32 * row_height;
+            let rect = Rect::at(x as i32, y as i32).of_size(column_width.ceil() as u32, row_height.ceil() as u32);
+            renderer.rect(rect, heat(chroma[pitch_class]));
+        }
+    }
+
+    for pitch_class in 0..12 {
+        let y = top + (11 - pitch_class) as f32 * row_height + row_height / 2.0 - 8.0 * scale;
+        renderer.text(((left * 0.2) as i32, y as i32), 16.0 * scale, Rgba([u8::MAX; 4]), Note::new(pitch_class as i32).name());
+    }
+}
+
+// Maps 0..1 onto black, through red and orange, to white
+fn heat(value: f32) -> Rgba<u8> {
+    let value = value.clamp(0.0, 1.0);
+    let channel = |from: f32| ((value - from) / (1.0 - from)).clamp(0.0, 1.0);
+    Rgba([
+        (channel(0.0) * 255.0) as u8,
+        (channel(0.4) * 255.0) as u8,
+        (channel(0.8) * 255.0) as u8,
+        u8::MAX
+    ])
+}
+
+fn draw_bands<X, Y>(renderer: &mut dyn Renderer, bands: &[Band], scaling: Scaling, tilt: Tilt, hertz_to_x: X, dbfs_to_y: Y, (start, end): (f32, f32))
+where
+    X: Fn(f32) -> f32,
+    Y: Fn(f32) -> f32
+{
+    let bottom = dbfs_to_y(f32::NEG_INFINITY);
+    for band in bands {
+        let Some(level) = scaling.power_level(band.power, Hertz(band.upper.0 - band.lower.0)) else { continue };
+
+        // leave a pixel of space between neighbouring bars
+        let left = (hertz_to_x(band.lower.0) + 1.0).max(start);
+        let right = (hertz_to_x(band.upper.0) - 1.0).min(end);
+        let top = dbfs_to_y(level + tilt.gain_db(band.centre));
+        if right <= left || bottom <= top {
+            continue;
+        }
+
+        let rect = Rect::at(left as i32, top as i32).of_size(((right - left) as u32).max(1), ((bottom - top) as u32).max(1));
+        renderer.rect(rect, Rgba([96, 160, 255, u8::MAX]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::audio::generator::{Generator, Signal};
+    use crate::audio::introspect::introspect;
+    use crate::fft::fft::Hertz;
+    use crate::fft::welch::Welch;
+    use crate::numtools::from_dbfs;
+    use crate::visualiser::Visualiser;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    // A visualiser after a few frames of a full second of the signal
+    fn visualise(signal: Signal, visualiser: &mut Visualiser) {
+        let (introspect, mut source) = introspect(Generator::new(signal, SAMPLE_RATE), Duration::from_secs(1));
+        source.by_ref().take(SAMPLE_RATE as usize).for_each(drop);
+
+        for _ in 0..3 {
+            visualiser.update(&introspect, 1.0 / 30.0);
+        }
+    }
+
+    #[test]
+    fn sine_reads_at_its_level_and_frequency() {
+        let mut visualiser = Visualiser { show_peaks: true, ..Visualiser::default() };
+        visualise(Signal::Sine { frequency: Hertz(1000.0), amplitude: from_dbfs(-6.0) }, &mut visualiser);
+
+        let peak = visualiser.peaks[0];
+        let level = visualiser.scaling.level(&visualiser.spectra[0], peak.bin, peak.amplitude).unwrap();
+        assert!((peak.frequency.0 - 1000.0).abs() < 0.1, "peak at {} Hz", peak.frequency.0);
+        assert!((level + 6.0).abs() < 0.1, "peak at {level} dBFS");
+
+        // the mean square of a sine is half its squared amplitude
+        let power = visualiser.power / (from_dbfs(-6.0).powi(2) / 2.0);
+        assert!((power - 1.0).abs() < 0.01, "power off by a factor of {power}");
+    }
+
+    #[test]
+    fn calibrating_on_a_sine_takes_its_rms_level() {
+        // a -6 dBFS sine is at -9 dBFS RMS, so reading 94 dB SPL puts 0 dBFS RMS at 103 dB SPL
+        let expected = 94.0 + 6.0 + 10.0 * 2f32.log10();
+        for welch in [None, Some(Welch::default())] {
+            let mut visualiser = Visualiser { welch, ..Visualiser::default() };
+            visualise(Signal::Sine { frequency: Hertz(1000.0), amplitude: from_dbfs(-6.0) }, &mut visualiser);
+
+            let offset = visualiser.calibrate(94.0).unwrap().offset;
+            assert!((offset - expected).abs() < 0.1, "calibrated to {offset} dB, expected {expected} dB ({welch:?})");
+        }
+    }
+}